@@ -0,0 +1,304 @@
+mod keystore;
+
+pub use keystore::{Keystore, DEFAULT_SCRYPT_LOG_N};
+
+use crate::address::{sighash_script, Address, AddressError, AddressFormat, AddressNetwork};
+use crate::project::{ChainType, Environment, TrampolineProject, TRAMPOLINE_FOLDER};
+
+use ckb_crypto::secp::{Generator, Privkey};
+use ckb_hash::blake2b_256;
+use ckb_types::{packed, prelude::*, H256};
+use std::fmt::Formatter;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub const ACCOUNTS_FOLDER: &str = "accounts";
+
+#[derive(Debug, Error)]
+pub enum AccountError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Secp(#[from] ckb_crypto::secp::Error),
+    #[error(transparent)]
+    Address(#[from] AddressError),
+    #[error("Account {0} already exists")]
+    AlreadyExists(String),
+    #[error("No account named {0}. See `trampoline account list`")]
+    NotFound(String),
+    #[error("Invalid account name {0}: use letters, digits, `-` and `_`")]
+    InvalidName(String),
+    #[error("Invalid private key: {0}")]
+    InvalidPrivkey(String),
+    #[error("Invalid keystore: {0}")]
+    InvalidKeystore(String),
+    #[error("Wrong password")]
+    WrongPassword,
+}
+
+pub type AccountResult<T> = std::result::Result<T, AccountError>;
+
+/// Public details of a secp256k1-blake160 account.
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub name: String,
+    pub lock_arg: [u8; 20],
+    // Decides the address prefix
+    pub chain: ChainType,
+}
+
+impl Account {
+    pub fn from_secret(name: &str, secret: &[u8; 32], chain: ChainType) -> AccountResult<Self> {
+        let pubkey = Privkey::from_slice(secret).pubkey()?;
+        let mut lock_arg = [0u8; 20];
+        lock_arg.copy_from_slice(&blake2b_256(pubkey.serialize())[0..20]);
+        Ok(Account {
+            name: name.to_string(),
+            lock_arg,
+            chain,
+        })
+    }
+
+    pub fn lock_script(&self) -> packed::Script {
+        sighash_script(&self.lock_arg)
+    }
+
+    pub fn lock_hash(&self) -> H256 {
+        self.lock_script().calc_script_hash().unpack()
+    }
+
+    pub fn address(&self) -> Address {
+        Address::new(AddressNetwork::from(self.chain), self.lock_script())
+    }
+
+    pub fn short_address(&self) -> AccountResult<Address> {
+        Ok(Address::with_format(
+            AddressNetwork::from(self.chain),
+            self.lock_script(),
+            AddressFormat::Short,
+        )?)
+    }
+}
+
+impl std::fmt::Display for Account {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "name: {}", self.name)?;
+        writeln!(f, "lock_arg: 0x{}", hex::encode(self.lock_arg))?;
+        writeln!(f, "lock_hash: {:#x}", self.lock_hash())?;
+        writeln!(f, "address: {}", self.address())?;
+        match self.short_address() {
+            Ok(short) => write!(f, "short address: {}", short),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// Keystore files of one environment, one `<name>.json` per account.
+#[derive(Debug, Clone)]
+pub struct AccountStore {
+    dir: PathBuf,
+    chain: ChainType,
+}
+
+impl AccountStore {
+    pub fn new(dir: impl AsRef<Path>, chain: ChainType) -> Self {
+        AccountStore {
+            dir: dir.as_ref().to_path_buf(),
+            chain,
+        }
+    }
+
+    // Separate directories keep a key from being used against another environment's network
+    pub fn for_environment(project: &TrampolineProject, environment: &Environment) -> Self {
+        Self::new(
+            project
+                .root_dir
+                .join(TRAMPOLINE_FOLDER)
+                .join(ACCOUNTS_FOLDER)
+                .join(&environment.name),
+            environment.chain,
+        )
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, name: &str) -> AccountResult<PathBuf> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(AccountError::InvalidName(name.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).map(|path| path.exists()).unwrap_or(false)
+    }
+
+    /// Generates a new random key and stores it encrypted with `password`.
+    pub fn create(&self, name: &str, password: &str) -> AccountResult<Account> {
+        let secret = Generator::random_secret_key();
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&secret[..]);
+        self.import(name, &bytes, password)
+    }
+
+    pub fn import(&self, name: &str, secret: &[u8; 32], password: &str) -> AccountResult<Account> {
+        let account = Account::from_secret(name, secret, self.chain)?;
+        let keystore = Keystore::encrypt(secret, &account.lock_arg, password)?;
+        self.save(name, &keystore)?;
+        Ok(account)
+    }
+
+    /// Stores an existing keystore, checking that `password` unlocks it. The cleartext
+    /// lock arg is replaced with the one derived from the key.
+    pub fn import_keystore(
+        &self,
+        name: &str,
+        keystore: &Keystore,
+        password: &str,
+    ) -> AccountResult<Account> {
+        let secret = keystore.decrypt(password)?;
+        let account = Account::from_secret(name, &secret, self.chain)?;
+        let keystore = Keystore {
+            lock_arg: Some(hex::encode(account.lock_arg)),
+            ..keystore.clone()
+        };
+        self.save(name, &keystore)?;
+        Ok(account)
+    }
+
+    fn save(&self, name: &str, keystore: &Keystore) -> AccountResult<()> {
+        let path = self.path(name)?;
+        if path.exists() {
+            return Err(AccountError::AlreadyExists(name.to_string()));
+        }
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, serde_json::to_string_pretty(keystore)?)?;
+        Ok(())
+    }
+
+    pub fn keystore(&self, name: &str) -> AccountResult<Keystore> {
+        let path = self.path(name)?;
+        if !path.exists() {
+            return Err(AccountError::NotFound(name.to_string()));
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn get(&self, name: &str) -> AccountResult<Account> {
+        let keystore = self.keystore(name)?;
+        let lock_arg = keystore
+            .lock_arg
+            .and_then(|arg| hex::decode(arg.trim_start_matches("0x")).ok())
+            .filter(|arg| arg.len() == 20)
+            .ok_or_else(|| AccountError::InvalidKeystore("invalid lock_arg".to_string()))?;
+        let mut account = Account {
+            name: name.to_string(),
+            lock_arg: [0u8; 20],
+            chain: self.chain,
+        };
+        account.lock_arg.copy_from_slice(&lock_arg);
+        Ok(account)
+    }
+
+    pub fn list(&self) -> AccountResult<Vec<Account>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut names = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.iter().map(|name| self.get(name)).collect()
+    }
+
+    /// Decrypts the private key of an account.
+    pub fn export(&self, name: &str, password: &str) -> AccountResult<[u8; 32]> {
+        self.keystore(name)?.decrypt(password)
+    }
+
+    pub fn remove(&self, name: &str) -> AccountResult<()> {
+        let path = self.path(name)?;
+        if !path.exists() {
+            return Err(AccountError::NotFound(name.to_string()));
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+/// Parses a hex private key, as written by `ckb-cli account export`.
+/// Only the first line is read, so ckb-cli's extended key files also work.
+pub fn parse_privkey(raw: &str) -> AccountResult<[u8; 32]> {
+    let line = raw.lines().next().unwrap_or_default().trim();
+    let bytes = hex::decode(line.trim_start_matches("0x"))
+        .map_err(|e| AccountError::InvalidPrivkey(e.to_string()))?;
+    if bytes.len() != 32 {
+        return Err(AccountError::InvalidPrivkey(format!(
+            "expected 32 bytes, got {}",
+            bytes.len()
+        )));
+    }
+    let mut secret = [0u8; 32];
+    secret.copy_from_slice(&bytes);
+    Privkey::from_slice(&secret).pubkey()?;
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Second genesis account of ckb's bundled dev spec
+    const DEV_PRIVKEY: &str = "0x63d86723e08f0f813a36ce6aa123bb2289d90680ae1e99d4de8cdb334553f24d";
+
+    #[test]
+    fn test_account_from_dev_privkey() {
+        let secret = parse_privkey(DEV_PRIVKEY).unwrap();
+        let account = Account::from_secret("dev", &secret, ChainType::Dev).unwrap();
+        assert_eq!(
+            hex::encode(account.lock_arg),
+            "470dcdc5e44064909650113a274b3b36aecb6dc7"
+        );
+        assert_eq!(
+            account.short_address().unwrap().to_string(),
+            "ckt1qyqywrwdchjyqeysjegpzw38fvandtktdhrs0zaxl4"
+        );
+    }
+
+    #[test]
+    fn test_import_keystore_derives_lock_arg() {
+        let dir = std::env::temp_dir().join(format!("trampoline-accounts-{}", std::process::id()));
+        let store = AccountStore::new(&dir, ChainType::Dev);
+        let secret = parse_privkey(DEV_PRIVKEY).unwrap();
+        let mut tampered = Keystore::encrypt_with(&secret, &[1u8; 20], "pass", 10).unwrap();
+        store
+            .import_keystore("tampered", &tampered, "pass")
+            .unwrap();
+        tampered.lock_arg = None;
+        store
+            .import_keystore("standard", &tampered, "pass")
+            .unwrap();
+        let accounts = store.list().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(accounts.len(), 2);
+        for account in accounts {
+            assert_eq!(
+                hex::encode(account.lock_arg),
+                "470dcdc5e44064909650113a274b3b36aecb6dc7"
+            );
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use super::{AccountError, AccountResult};
+
+use aes::cipher::{NewCipher, StreamCipher};
+use ckb_hash::blake2b_256;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Keccak};
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+const KEYSTORE_VERSION: u32 = 3;
+const CIPHER: &str = "aes-128-ctr";
+const KDF: &str = "scrypt";
+const DKLEN: usize = 32;
+// Same cost as geth's "standard" keystores
+pub const DEFAULT_SCRYPT_LOG_N: u8 = 18;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// An encrypted private key in the Web3 Secret Storage (Ethereum keystore v3)
+/// layout. As in ckb-cli, the MAC is written with blake2b-256; keystores with the
+/// keccak-256 MAC of Ethereum tools are read as well.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Keystore {
+    pub version: u32,
+    pub id: String,
+    // Lock args of the key, kept in clear so accounts can be listed without a password.
+    // Not part of the v3 layout, so keystores exported by other tools lack it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_arg: Option<String>,
+    pub crypto: Crypto,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Crypto {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub mac: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KdfParams {
+    pub dklen: usize,
+    pub n: u64,
+    pub r: u32,
+    pub p: u32,
+    pub salt: String,
+}
+
+impl Keystore {
+    pub fn encrypt(secret: &[u8; 32], lock_arg: &[u8], password: &str) -> AccountResult<Self> {
+        Self::encrypt_with(secret, lock_arg, password, DEFAULT_SCRYPT_LOG_N)
+    }
+
+    pub fn encrypt_with(
+        secret: &[u8; 32],
+        lock_arg: &[u8],
+        password: &str,
+        scrypt_log_n: u8,
+    ) -> AccountResult<Self> {
+        let mut rng = rand::thread_rng();
+        let mut salt = [0u8; 32];
+        let mut iv = [0u8; 16];
+        rng.fill_bytes(&mut salt);
+        rng.fill_bytes(&mut iv);
+
+        let kdfparams = KdfParams {
+            dklen: DKLEN,
+            n: 1 << scrypt_log_n,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+            salt: hex::encode(salt),
+        };
+        let derived_key = derive_key(password, &kdfparams)?;
+
+        let mut ciphertext = secret.to_vec();
+        apply_cipher(&derived_key[..16], &iv, &mut ciphertext)?;
+
+        Ok(Keystore {
+            version: KEYSTORE_VERSION,
+            id: uuid::Uuid::new_v4().to_string(),
+            lock_arg: Some(hex::encode(lock_arg)),
+            crypto: Crypto {
+                cipher: CIPHER.to_string(),
+                cipherparams: CipherParams {
+                    iv: hex::encode(iv),
+                },
+                mac: hex::encode(mac(&derived_key, &ciphertext)),
+                ciphertext: hex::encode(ciphertext),
+                kdf: KDF.to_string(),
+                kdfparams,
+            },
+        })
+    }
+
+    pub fn decrypt(&self, password: &str) -> AccountResult<[u8; 32]> {
+        let crypto = &self.crypto;
+        if crypto.cipher != CIPHER || crypto.kdf != KDF {
+            return Err(AccountError::InvalidKeystore(format!(
+                "unsupported cipher {} or kdf {}",
+                crypto.cipher, crypto.kdf
+            )));
+        }
+        let derived_key = derive_key(password, &crypto.kdfparams)?;
+        let mut ciphertext = decode_hex(&crypto.ciphertext)?;
+        // The layout does not name the MAC hash, so either one may match
+        let expected = decode_hex(&crypto.mac)?;
+        if mac(&derived_key, &ciphertext) != expected.as_slice()
+            && keccak_mac(&derived_key, &ciphertext) != expected.as_slice()
+        {
+            return Err(AccountError::WrongPassword);
+        }
+
+        let iv = decode_hex(&crypto.cipherparams.iv)?;
+        apply_cipher(&derived_key[..16], &iv, &mut ciphertext)?;
+        let mut secret = [0u8; 32];
+        if ciphertext.len() != secret.len() {
+            return Err(AccountError::InvalidKeystore(
+                "private key must be 32 bytes".to_string(),
+            ));
+        }
+        secret.copy_from_slice(&ciphertext);
+        Ok(secret)
+    }
+}
+
+fn derive_key(password: &str, kdfparams: &KdfParams) -> AccountResult<Vec<u8>> {
+    if !kdfparams.n.is_power_of_two() || kdfparams.dklen < DKLEN {
+        return Err(AccountError::InvalidKeystore(
+            "invalid scrypt parameters".to_string(),
+        ));
+    }
+    let log_n = kdfparams.n.trailing_zeros() as u8;
+    let params = scrypt::Params::new(log_n, kdfparams.r, kdfparams.p)
+        .map_err(|e| AccountError::InvalidKeystore(e.to_string()))?;
+    let salt = decode_hex(&kdfparams.salt)?;
+    let mut derived_key = vec![0u8; kdfparams.dklen];
+    scrypt::scrypt(password.as_bytes(), &salt, &params, &mut derived_key)
+        .map_err(|e| AccountError::InvalidKeystore(e.to_string()))?;
+    Ok(derived_key)
+}
+
+fn apply_cipher(key: &[u8], iv: &[u8], data: &mut [u8]) -> AccountResult<()> {
+    let mut cipher = Aes128Ctr::new_from_slices(key, iv)
+        .map_err(|e| AccountError::InvalidKeystore(e.to_string()))?;
+    cipher.apply_keystream(data);
+    Ok(())
+}
+
+fn mac(derived_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut input = derived_key[16..32].to_vec();
+    input.extend_from_slice(ciphertext);
+    blake2b_256(input)
+}
+
+fn keccak_mac(derived_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(&derived_key[16..32]);
+    hasher.update(ciphertext);
+    let mut output = [0u8; 32];
+    hasher.finalize(&mut output);
+    output
+}
+
+fn decode_hex(value: &str) -> AccountResult<Vec<u8>> {
+    hex::decode(value.trim_start_matches("0x"))
+        .map_err(|e| AccountError::InvalidKeystore(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let secret = [7u8; 32];
+        let keystore = Keystore::encrypt_with(&secret, &[1u8; 20], "hunter2", 10).unwrap();
+        assert_eq!(keystore.decrypt("hunter2").unwrap(), secret);
+        assert!(matches!(
+            keystore.decrypt("wrong"),
+            Err(AccountError::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_keccak_mac() {
+        // Keccak-256 of the empty input, as opposed to SHA3-256
+        let mut hasher = Keccak::v256();
+        hasher.update(&[]);
+        let mut empty = [0u8; 32];
+        hasher.finalize(&mut empty);
+        assert_eq!(
+            hex::encode(empty),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+
+        let secret = [7u8; 32];
+        let mut keystore = Keystore::encrypt_with(&secret, &[1u8; 20], "hunter2", 10).unwrap();
+        let derived_key = derive_key("hunter2", &keystore.crypto.kdfparams).unwrap();
+        let ciphertext = decode_hex(&keystore.crypto.ciphertext).unwrap();
+        keystore.crypto.mac = hex::encode(keccak_mac(&derived_key, &ciphertext));
+        assert_eq!(keystore.decrypt("hunter2").unwrap(), secret);
+        assert!(matches!(
+            keystore.decrypt("wrong"),
+            Err(AccountError::WrongPassword)
+        ));
+    }
+}
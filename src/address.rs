@@ -0,0 +1,381 @@
+use crate::project::ChainType;
+
+use bech32::{FromBase32, ToBase32, Variant};
+use ckb_types::{bytes::Bytes, core::ScriptHashType, h256, packed, prelude::*, H256};
+use std::convert::TryFrom;
+use std::fmt::Formatter;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub const MAINNET_PREFIX: &str = "ckb";
+pub const TESTNET_PREFIX: &str = "ckt";
+
+pub const SIGHASH_TYPE_HASH: H256 =
+    h256!("0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8");
+pub const MULTISIG_TYPE_HASH: H256 =
+    h256!("0x5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8");
+pub const ACP_MAINNET_TYPE_HASH: H256 =
+    h256!("0xd369597ff47f29fbc0d47d2e3775370d1250b85140c670e4718af712983a2354");
+pub const ACP_TESTNET_TYPE_HASH: H256 =
+    h256!("0x3419a1c09eb2567f6552ee7a8ecffd64155cffe0f1796e6e61ec088d740c1356");
+
+// Payload format types from RFC 0021
+const FORMAT_FULL: u8 = 0x00;
+const FORMAT_SHORT: u8 = 0x01;
+const FORMAT_FULL_DATA: u8 = 0x02;
+const FORMAT_FULL_TYPE: u8 = 0x04;
+
+// Indexes of the code hashes a short address can refer to
+const SHORT_SIGHASH: u8 = 0x00;
+const SHORT_MULTISIG: u8 = 0x01;
+const SHORT_ACP: u8 = 0x02;
+
+#[derive(Debug, Error)]
+pub enum AddressError {
+    #[error(transparent)]
+    Bech32(#[from] bech32::Error),
+    #[error("Unknown address prefix {0}, expected ckb or ckt")]
+    UnknownPrefix(String),
+    #[error("Unknown address format type {0:#04x}")]
+    UnknownFormat(u8),
+    #[error("Address uses {found:?} encoding, but its format requires {expected:?}")]
+    WrongVariant { expected: Variant, found: Variant },
+    #[error("Invalid address payload: {0}")]
+    InvalidPayload(String),
+    #[error("Script cannot be encoded as a short address")]
+    NotShortEncodable,
+}
+
+pub type AddressResult<T> = std::result::Result<T, AddressError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressNetwork {
+    Mainnet,
+    Testnet,
+}
+
+impl AddressNetwork {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Self::Mainnet => MAINNET_PREFIX,
+            Self::Testnet => TESTNET_PREFIX,
+        }
+    }
+
+    pub fn from_prefix(prefix: &str) -> AddressResult<Self> {
+        match prefix {
+            MAINNET_PREFIX => Ok(Self::Mainnet),
+            TESTNET_PREFIX => Ok(Self::Testnet),
+            _ => Err(AddressError::UnknownPrefix(prefix.to_string())),
+        }
+    }
+}
+
+// Dev chains use testnet addresses
+impl From<ChainType> for AddressNetwork {
+    fn from(chain: ChainType) -> Self {
+        if chain.is_mainnet() {
+            Self::Mainnet
+        } else {
+            Self::Testnet
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFormat {
+    /// Deprecated, only for the sighash, multisig and anyone-can-pay locks.
+    Short,
+    /// Deprecated, `hash_type` data.
+    FullData,
+    /// Deprecated, `hash_type` type.
+    FullType,
+    /// Any script, bech32m encoded.
+    Full,
+}
+
+impl AddressFormat {
+    fn variant(&self) -> Variant {
+        match self {
+            Self::Full => Variant::Bech32m,
+            _ => Variant::Bech32,
+        }
+    }
+}
+
+/// A lock script together with the network and format it is written for.
+#[derive(Debug, Clone)]
+pub struct Address {
+    network: AddressNetwork,
+    script: packed::Script,
+    format: AddressFormat,
+}
+
+impl Address {
+    /// An address in the full format, which can encode any script.
+    pub fn new(network: AddressNetwork, script: packed::Script) -> Self {
+        Address {
+            network,
+            script,
+            format: AddressFormat::Full,
+        }
+    }
+
+    pub fn with_format(
+        network: AddressNetwork,
+        script: packed::Script,
+        format: AddressFormat,
+    ) -> AddressResult<Self> {
+        let address = Address {
+            network,
+            script,
+            format,
+        };
+        // Rejects scripts the format cannot represent
+        address.payload()?;
+        Ok(address)
+    }
+
+    /// The default secp256k1-blake160 lock for a 20 byte lock arg.
+    pub fn from_lock_arg(network: AddressNetwork, lock_arg: &[u8]) -> Self {
+        Self::new(network, sighash_script(lock_arg))
+    }
+
+    pub fn network(&self) -> AddressNetwork {
+        self.network
+    }
+
+    pub fn script(&self) -> &packed::Script {
+        &self.script
+    }
+
+    pub fn format(&self) -> AddressFormat {
+        self.format
+    }
+
+    pub fn encode(&self) -> AddressResult<String> {
+        Ok(bech32::encode(
+            self.network.prefix(),
+            self.payload()?.to_base32(),
+            self.format.variant(),
+        )?)
+    }
+
+    pub fn decode(address: &str) -> AddressResult<Self> {
+        let (prefix, data, variant) = bech32::decode(address)?;
+        let network = AddressNetwork::from_prefix(&prefix)?;
+        let payload = Vec::<u8>::from_base32(&data)?;
+        let (format_type, body) = payload
+            .split_first()
+            .ok_or_else(|| AddressError::InvalidPayload("empty payload".to_string()))?;
+
+        let (format, script) = match *format_type {
+            FORMAT_SHORT => {
+                let (index, args) = body.split_first().ok_or_else(|| {
+                    AddressError::InvalidPayload("missing code hash index".into())
+                })?;
+                let code_hash = short_code_hash(*index, network, args.len())?;
+                (
+                    AddressFormat::Short,
+                    script(&code_hash, ScriptHashType::Type, args),
+                )
+            }
+            FORMAT_FULL_DATA | FORMAT_FULL_TYPE => {
+                let (code_hash, args) = split_code_hash(body)?;
+                let (format, hash_type) = if *format_type == FORMAT_FULL_DATA {
+                    (AddressFormat::FullData, ScriptHashType::Data)
+                } else {
+                    (AddressFormat::FullType, ScriptHashType::Type)
+                };
+                (format, script(&code_hash, hash_type, args))
+            }
+            FORMAT_FULL => {
+                let (code_hash, rest) = split_code_hash(body)?;
+                let (hash_type, args) = rest
+                    .split_first()
+                    .ok_or_else(|| AddressError::InvalidPayload("missing hash type".into()))?;
+                let hash_type = ScriptHashType::try_from(*hash_type).map_err(|_| {
+                    AddressError::InvalidPayload(format!("invalid hash type {}", hash_type))
+                })?;
+                (AddressFormat::Full, script(&code_hash, hash_type, args))
+            }
+            other => return Err(AddressError::UnknownFormat(other)),
+        };
+
+        if variant != format.variant() {
+            return Err(AddressError::WrongVariant {
+                expected: format.variant(),
+                found: variant,
+            });
+        }
+        Ok(Address {
+            network,
+            script,
+            format,
+        })
+    }
+
+    fn payload(&self) -> AddressResult<Vec<u8>> {
+        let code_hash: H256 = self.script.code_hash().unpack();
+        let hash_type = self.script.hash_type();
+        let args = self.script.args().raw_data();
+        let mut payload = vec![];
+        match self.format {
+            AddressFormat::Short => {
+                let index = short_index(&code_hash, self.network, args.len())
+                    .filter(|_| hash_type == ScriptHashType::Type.into())
+                    .ok_or(AddressError::NotShortEncodable)?;
+                payload.extend_from_slice(&[FORMAT_SHORT, index]);
+            }
+            AddressFormat::FullData | AddressFormat::FullType => {
+                let (format_type, expected) = if self.format == AddressFormat::FullData {
+                    (FORMAT_FULL_DATA, ScriptHashType::Data)
+                } else {
+                    (FORMAT_FULL_TYPE, ScriptHashType::Type)
+                };
+                if hash_type != expected.into() {
+                    return Err(AddressError::InvalidPayload(format!(
+                        "{:?} addresses require hash_type {:?}",
+                        self.format, expected
+                    )));
+                }
+                payload.push(format_type);
+                payload.extend_from_slice(code_hash.as_bytes());
+            }
+            AddressFormat::Full => {
+                payload.push(FORMAT_FULL);
+                payload.extend_from_slice(code_hash.as_bytes());
+                payload.push(hash_type.into());
+            }
+        }
+        payload.extend_from_slice(&args);
+        Ok(payload)
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::decode(s)
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Formats are validated on construction, so encoding cannot fail
+        write!(f, "{}", self.encode().map_err(|_| std::fmt::Error)?)
+    }
+}
+
+impl From<Address> for packed::Script {
+    fn from(address: Address) -> Self {
+        address.script
+    }
+}
+
+pub fn sighash_script(lock_arg: &[u8]) -> packed::Script {
+    script(&SIGHASH_TYPE_HASH, ScriptHashType::Type, lock_arg)
+}
+
+fn script(code_hash: &H256, hash_type: ScriptHashType, args: &[u8]) -> packed::Script {
+    packed::Script::new_builder()
+        .code_hash(code_hash.pack())
+        .hash_type(hash_type.into())
+        .args(Bytes::copy_from_slice(args).pack())
+        .build()
+}
+
+fn split_code_hash(body: &[u8]) -> AddressResult<(H256, &[u8])> {
+    if body.len() < 32 {
+        return Err(AddressError::InvalidPayload(
+            "code hash too short".to_string(),
+        ));
+    }
+    let (code_hash, rest) = body.split_at(32);
+    let code_hash = H256::from_slice(code_hash)
+        .map_err(|e| AddressError::InvalidPayload(format!("{:?}", e)))?;
+    Ok((code_hash, rest))
+}
+
+fn short_code_hash(index: u8, network: AddressNetwork, args_len: usize) -> AddressResult<H256> {
+    let invalid_args = || AddressError::InvalidPayload(format!("invalid args length {}", args_len));
+    match index {
+        SHORT_SIGHASH | SHORT_MULTISIG if args_len != 20 => Err(invalid_args()),
+        SHORT_SIGHASH => Ok(SIGHASH_TYPE_HASH),
+        SHORT_MULTISIG => Ok(MULTISIG_TYPE_HASH),
+        SHORT_ACP if !(20..=22).contains(&args_len) => Err(invalid_args()),
+        SHORT_ACP => Ok(acp_type_hash(network)),
+        other => Err(AddressError::InvalidPayload(format!(
+            "unknown code hash index {}",
+            other
+        ))),
+    }
+}
+
+fn short_index(code_hash: &H256, network: AddressNetwork, args_len: usize) -> Option<u8> {
+    if *code_hash == SIGHASH_TYPE_HASH && args_len == 20 {
+        Some(SHORT_SIGHASH)
+    } else if *code_hash == MULTISIG_TYPE_HASH && args_len == 20 {
+        Some(SHORT_MULTISIG)
+    } else if *code_hash == acp_type_hash(network) && (20..=22).contains(&args_len) {
+        Some(SHORT_ACP)
+    } else {
+        None
+    }
+}
+
+fn acp_type_hash(network: AddressNetwork) -> H256 {
+    match network {
+        AddressNetwork::Mainnet => ACP_MAINNET_TYPE_HASH,
+        AddressNetwork::Testnet => ACP_TESTNET_TYPE_HASH,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK_ARG: &str = "b39bbc0b3673c7d36450bc14cfcdad2d559c6c64";
+
+    #[test]
+    fn test_address_formats_roundtrip() {
+        let lock_arg = hex::decode(LOCK_ARG).unwrap();
+        let script = sighash_script(&lock_arg);
+        let cases = [
+            (
+                AddressFormat::Short,
+                "ckb1qyqt8xaupvm8837nv3gtc9x0ekkj64vud3jqfwyw5v",
+            ),
+            (
+                AddressFormat::FullType,
+                "ckb1qjda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xw3vumhs9nvu786dj9p0q5elx66t24n3kxgj53qks",
+            ),
+            (
+                AddressFormat::Full,
+                "ckb1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqdnnw7qkdnnclfkg59uzn8umtfd2kwxceqxwquc4",
+            ),
+        ];
+        for (format, encoded) in cases {
+            let address =
+                Address::with_format(AddressNetwork::Mainnet, script.clone(), format).unwrap();
+            assert_eq!(address.encode().unwrap(), encoded);
+            let decoded = Address::decode(encoded).unwrap();
+            assert_eq!(decoded.format(), format);
+            assert_eq!(decoded.script(), &script);
+        }
+    }
+
+    #[test]
+    fn test_reject_wrong_variant() {
+        // Full format payload encoded with bech32 instead of bech32m
+        let lock_arg = hex::decode(LOCK_ARG).unwrap();
+        let address = Address::new(AddressNetwork::Testnet, sighash_script(&lock_arg));
+        let payload = address.payload().unwrap();
+        let encoded = bech32::encode(TESTNET_PREFIX, payload.to_base32(), Variant::Bech32).unwrap();
+        assert!(matches!(
+            Address::decode(&encoded),
+            Err(AddressError::WrongVariant { .. })
+        ));
+    }
+}
@@ -1,42 +1,159 @@
-use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Result;
 use ckb_app_config::BlockAssemblerConfig;
 use ckb_hash::blake2b_256;
 
-use ckb_types::{h256, H256};
+use ckb_jsonrpc_types::Transaction;
+use ckb_types::{core::ScriptHashType, packed, prelude::*, H256};
+use std::convert::TryFrom;
 
 use structopt::StructOpt;
 
+use trampoline::account::{parse_privkey, AccountStore, Keystore};
+use trampoline::address::{sighash_script, Address, AddressNetwork};
 use trampoline::docker::*;
-use trampoline::opts::{NetworkCommands, SchemaCommand, TrampolineCommand};
-use trampoline::parse_hex;
+use trampoline::faucet::{Faucet, DEV_FAUCET_PRIVKEY};
+use trampoline::network::{backend_for, NetworkService};
+use trampoline::opts::{
+    AccountCommand, NetworkCommands, PatternCommand, SchemaCommand, ScriptCommand,
+    TrampolineCommand, TrampolineOpts, TxCommand,
+};
+use trampoline::pattern::Pattern;
 use trampoline::project::*;
+use trampoline::rpc::{BlockingRpcClient, RpcClient};
 use trampoline::schema::{Schema, SchemaInitArgs};
+use trampoline::script::{self, BuildProfile, Deployer, Script, ScriptError};
+use trampoline::transaction::{input_locks, MultisigConfig, Signer};
 use trampoline::TrampolineResource;
 use trampoline::TrampolineResourceType;
+use trampoline::{hex_string, parse_hex};
 
-const SECP_TYPE_HASH: H256 =
-    h256!("0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8");
-fn create_block_assembler_from_pkhash(hash: &[u8]) -> BlockAssemblerConfig {
-    use ckb_jsonrpc_types::{JsonBytes, ScriptHashType};
-    BlockAssemblerConfig {
-        code_hash: SECP_TYPE_HASH,
-        hash_type: ScriptHashType::Type,
+const ONE_CKB: u64 = 100_000_000;
+
+// Read instead of prompting when set, so scripts can manage accounts
+const PASSWORD_ENV: &str = "TRAMPOLINE_PASSWORD";
+
+fn create_block_assembler(script: &packed::Script) -> Result<BlockAssemblerConfig> {
+    use ckb_jsonrpc_types::JsonBytes;
+    let hash_type = ScriptHashType::try_from(script.hash_type())
+        .map_err(|_| anyhow!("Invalid hash type in miner lock script"))?;
+    Ok(BlockAssemblerConfig {
+        code_hash: script.code_hash().unpack(),
+        hash_type: hash_type.into(),
         use_binary_version_as_message_prefix: false,
-        args: JsonBytes::from_bytes(bytes::Bytes::copy_from_slice(hash)),
+        args: JsonBytes::from_bytes(script.args().raw_data()),
         message: JsonBytes::default(),
         binary_version: "".to_string(),
+    })
+}
+
+fn read_password(confirm: bool) -> Result<String> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(password);
+    }
+    let password = rpassword::read_password_from_tty(Some("Password: "))?;
+    if confirm {
+        let repeat = rpassword::read_password_from_tty(Some("Repeat password: "))?;
+        if password != repeat {
+            return Err(anyhow!("Passwords do not match"));
+        }
+    }
+    Ok(password)
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn configure_network(
+    project: &mut TrampolineProject,
+    service: NetworkService,
+    update: &VirtualEnvUpdate,
+    show: bool,
+) -> Result<()> {
+    if !update.is_empty() {
+        project.update_virtual_env(&service.to_string(), update)?;
+        println!("Updated [{}] in {}", service, TRAMPOLINE_ENV_CONFIG);
+        // Only a hint, so a missing container runtime is not an error here
+        let running = backend_for(project)
+            .ok()
+            .and_then(|network| network.service_status(service).ok())
+            .is_some_and(|status| status.running);
+        if running {
+            println!(
+                "{} is running. Restart it for the changes to take effect",
+                service
+            );
+        }
+    }
+    if show || update.is_empty() {
+        for service in NetworkService::all() {
+            let env = service
+                .virtual_env(project)
+                .ok_or_else(|| anyhow!("Missing network configuration in trampoline-env.toml"))?;
+            println!("[{}]", service);
+            println!("rpc: {}", service.rpc_url(project).unwrap_or_default());
+            println!("container port: {}", env.container_port);
+            println!(
+                "data: {} -> {}\n",
+                project.root_dir.join(&env.local_binding).display(),
+                env.container_mount
+            );
+        }
+    }
+    Ok(())
+}
+
+fn build_profile(release: bool) -> BuildProfile {
+    if release {
+        BuildProfile::Release
+    } else {
+        BuildProfile::Debug
+    }
+}
+
+// Signs with the account if given, else with the dev chain's genesis key
+fn deployment_signer(
+    project: &TrampolineProject,
+    environment: &Environment,
+    account: Option<String>,
+) -> Result<Signer> {
+    let secret = match account {
+        Some(account) => AccountStore::for_environment(project, environment)
+            .export(&account, &read_password(false)?)?,
+        None if environment.chain == ChainType::Dev => parse_privkey(DEV_FAUCET_PRIVKEY)?,
+        None => return Err(anyhow!("Pass --account to pay for the deployment")),
+    };
+    Ok(Signer::new(&secret)?)
+}
+
+fn print_deployment(name: &str, deployed: &DeployedScript) {
+    println!(
+        "Deployed {} at {:#x}:{}",
+        name, deployed.tx_hash, deployed.index
+    );
+    println!("data_hash: {:#x}", deployed.data_hash);
+    if let Some(type_hash) = &deployed.type_hash {
+        println!("type_hash: {:#x}", type_hash);
+    }
+    if !deployed.history.is_empty() {
+        println!("previous versions: {}", deployed.history.len());
     }
 }
 
 fn main() -> Result<()> {
-    let opts = TrampolineCommand::from_args();
+    let opts = TrampolineOpts::from_args();
 
     let project = TrampolineProject::load(std::env::current_dir()?);
 
-    match opts {
+    match opts.command {
         TrampolineCommand::NewProject { name } => match project {
             Ok(project) => {
                 if let TrampolineResourceType::Project(project) = project {
@@ -50,7 +167,11 @@ fn main() -> Result<()> {
             Err(_e) => {
                 let project = TrampolineProject::from(TrampolineProject::init(name)?);
                 std::env::set_current_dir(&project.root_dir)?;
-                Docker::default().build()?;
+                let runtime = project.config.env.as_ref().map(|env| &env.runtime);
+                backend_from_config(runtime)?.build_image(
+                    &project.root_dir,
+                    runtime.map(|r| r.image()).unwrap_or(IMAGE_NAME),
+                )?;
             }
         },
         TrampolineCommand::Schema { command } => {
@@ -74,110 +195,442 @@ fn main() -> Result<()> {
             }
         }
         TrampolineCommand::Network { command } => {
-            let project = TrampolineProject::from(project?);
+            let mut project = TrampolineProject::from(project?);
+            let environment = project.environment(&opts.env)?;
+            if !environment.local {
+                return Err(anyhow!(
+                    "`network` manages the local chain, but environment {} points at {}",
+                    environment.name,
+                    environment.ckb_rpc
+                ));
+            }
+            if let NetworkCommands::Config {
+                service,
+                port_host,
+                host,
+                local_binding,
+                show,
+            } = command
+            {
+                let update = VirtualEnvUpdate {
+                    host,
+                    host_port: port_host,
+                    local_binding,
+                };
+                return configure_network(&mut project, service, &update, show);
+            }
+            let network = backend_for(&project)?;
             match command {
-                NetworkCommands::Launch {} => {
-                    let image = DockerImage {
-                        name: "iamm/trampoline-env".to_string(),
-                        tag: Some("latest".to_string()),
-                        file_path: Some(".".to_string()),
-                        host_mappings: vec![],
-                        build_args: HashMap::new(),
-                    };
-
-                    let cmd: DockerCommand<DockerImage> =
-                        DockerCommand::default().build(&image, true).unwrap();
-                    cmd.execute(None)?;
-
-                    let container_port = project.config.env.as_ref().unwrap().chain.container_port;
-                    let host_port = project.config.env.as_ref().unwrap().chain.host_port;
-
-                    let host_volume = project
-                        .config
-                        .env
-                        .as_ref()
-                        .unwrap()
-                        .chain
-                        .local_binding
-                        .as_path();
-
-                    let container_volume = project
-                        .config
-                        .env
-                        .as_ref()
-                        .unwrap()
-                        .chain
-                        .container_mount
-                        .as_str();
-
-                    let docker_volume = Volume {
-                        host: &host_volume,
-                        container: std::path::Path::new(container_volume),
-                    };
-
-                    let container = DockerContainer {
-                        name: project.config.name.clone(),
-                        port_bindings: vec![DockerPort {
-                            host: host_port,
-                            container: container_port,
-                        }],
-                        volumes: vec![docker_volume],
-                        env_vars: HashMap::default(),
-                        image: image,
-                    };
-                    let run: DockerCommand<DockerContainer> = DockerCommand::default()
-                        .run(&container, false, true)
-                        .unwrap();
-
-                    run.execute(Some(vec!["run".to_string()]))?;
-                    // Docker::default()
-                    //     .name(project.config.name.as_str())
-                    //     .add_service(project.config.env.unwrap().chain)?
-                    //     .run(
-                    //         Some(vec!["run".to_string()]),
-                    //         Some("node"),
-                    //         vec![miner_ports],
-                    //     )?;
-                }
-                NetworkCommands::SetMiner { pubkey, lock_arg } => {
-                    let mut config = project.load_ckb_config()?;
-                    if let Some(pubkey) = pubkey {
+                NetworkCommands::Launch {
+                    attach,
+                    timeout,
+                    no_wait,
+                } => {
+                    network.init(false)?;
+                    network.launch(attach)?;
+                    if !attach && !no_wait {
+                        network.wait_until_ready(
+                            NetworkService::Chain,
+                            Duration::from_secs(timeout),
+                        )?;
+                    }
+                }
+                NetworkCommands::SetMiner {
+                    pubkey,
+                    lock_arg,
+                    address,
+                } => {
+                    let lock_script = if let Some(address) = address {
+                        let address = Address::decode(&address)?;
+                        if address.network() == AddressNetwork::Mainnet {
+                            eprintln!("Warning: {} is a mainnet address", address);
+                        }
+                        address.script().clone()
+                    } else if let Some(pubkey) = pubkey {
                         let pubkey_bytes = parse_hex(pubkey.as_str())?;
                         let pubkey_hash = blake2b_256(&pubkey_bytes);
-                        let pkey_hash_slice = &pubkey_hash[0..20];
-                        let block_assembler = create_block_assembler_from_pkhash(pkey_hash_slice);
-                        config.block_assembler = Some(block_assembler);
-                        project.save_ckb_config(config)?;
-                    } else if let Some(lock_arg) = lock_arg {
-                        let lock_arg_bytes = parse_hex(lock_arg.as_str())?;
-                        let block_assembler = create_block_assembler_from_pkhash(&lock_arg_bytes);
-                        config.block_assembler = Some(block_assembler);
-                        project.save_ckb_config(config)?;
-                    }
-                    Docker::default()
-                        .name(format!("{}-node", project.config.name.as_str()).as_str())
-                        .restart()?;
-                }
-                NetworkCommands::Miner { one_block: _ } => {
+                        sighash_script(&pubkey_hash[0..20])
+                    } else {
+                        let lock_arg = lock_arg.expect("required unless pubkey or address");
+                        sighash_script(&parse_hex(lock_arg.as_str())?)
+                    };
+                    // The node config is generated by `init`, which may not have run yet
+                    network.init(false)?;
+                    let mut config = project.load_ckb_config()?;
+                    config.block_assembler = Some(create_block_assembler(&lock_script)?);
+                    project.save_ckb_config(config)?;
+                    if network.service_status(NetworkService::Chain)?.running {
+                        network.restart(NetworkService::Chain)?;
+                    }
+                }
+                NetworkCommands::Miner {
+                    blocks,
+                    until_epoch,
+                    stop,
+                } => {
+                    if stop {
+                        network.stop(Some(NetworkService::Miner))?;
+                        return Ok(());
+                    }
                     let config = project.load_ckb_config()?;
                     let block_assembler_args = config.block_assembler.as_ref();
                     if block_assembler_args.is_none() {
                         return Err(anyhow!("No miner address set. Refer to `trampoline net set-miner --help` for more information."));
                     }
-                    let container_name = project.config.name.as_str();
-                    let miner_mount_path = &project.config.env.unwrap().miner.container_mount;
-                    Docker::exec(
-                        format!("{}-node", container_name).as_str(),
-                        vec!["ckb", "miner"],
-                        miner_mount_path,
-                    )?;
+                    if let Some(blocks) = blocks {
+                        network.mine_blocks(blocks)?;
+                        return Ok(());
+                    }
+                    if let Some(epoch) = until_epoch {
+                        network.mine_until_epoch(epoch)?;
+                        return Ok(());
+                    }
+                    network.start_miner()?;
+                }
+                NetworkCommands::Indexer { timeout, no_wait } => {
+                    network.launch_indexer()?;
+                    if !no_wait {
+                        network.wait_until_ready(
+                            NetworkService::Indexer,
+                            Duration::from_secs(timeout),
+                        )?;
+                    }
+                }
+                NetworkCommands::Stop { service } => {
+                    network.stop(service)?;
+                }
+                NetworkCommands::Status {} => {
+                    for status in network.status()? {
+                        println!("{}", status);
+                    }
+                }
+                NetworkCommands::Down {} => {
+                    network.down()?;
+                }
+                NetworkCommands::Logs {
+                    service,
+                    follow,
+                    since,
+                    tail,
+                } => {
+                    let options = LogOptions {
+                        follow,
+                        since,
+                        tail,
+                    };
+                    network.logs(service, &options)?;
+                }
+                NetworkCommands::Init { reset } => {
+                    network.init(reset)?;
                 }
-                NetworkCommands::Indexer {} => {}
                 _ => {}
             }
         }
-        _ => {
-            println!("Unrecognized command. Use `trampoline --help` for usage information.")
+        TrampolineCommand::Address { address, lock_arg } => {
+            let address = match (address, lock_arg) {
+                (Some(address), _) => Address::decode(&address)?,
+                (None, Some(lock_arg)) => {
+                    let chain = match project {
+                        Ok(project) => {
+                            TrampolineProject::from(project)
+                                .environment(&opts.env)?
+                                .chain
+                        }
+                        Err(_) => ChainType::default(),
+                    };
+                    Address::from_lock_arg(chain.into(), &parse_hex(&lock_arg)?)
+                }
+                (None, None) => unreachable!("address is required unless lock_arg"),
+            };
+            let script = address.script();
+            let lock_hash: H256 = script.calc_script_hash().unpack();
+            println!("address: {}", address);
+            println!("network: {:?}", address.network());
+            println!("format: {:?}", address.format());
+            println!("lock_hash: {:#x}", lock_hash);
+            println!(
+                "script: {}",
+                serde_json::to_string_pretty(&ckb_jsonrpc_types::Script::from(script.clone()))?
+            );
+        }
+        TrampolineCommand::Account { command } => {
+            let project = TrampolineProject::from(project?);
+            let environment = project.environment(&opts.env)?;
+            let store = AccountStore::for_environment(&project, &environment);
+            eprintln!("Environment: {}\n", environment);
+            match command {
+                AccountCommand::New { name } => {
+                    if store.exists(&name) {
+                        return Err(anyhow!("Account {} already exists", name));
+                    }
+                    let account = store.create(&name, &read_password(true)?)?;
+                    println!("{}", account);
+                }
+                AccountCommand::List {} => {
+                    for account in store.list()? {
+                        println!("{}\n", account);
+                    }
+                }
+                AccountCommand::Import {
+                    name,
+                    privkey_path,
+                    keystore,
+                } => {
+                    let account = if let Some(path) = keystore {
+                        let keystore: Keystore =
+                            serde_json::from_str(&std::fs::read_to_string(path)?)?;
+                        store.import_keystore(&name, &keystore, &read_password(false)?)?
+                    } else {
+                        let path = privkey_path.expect("required unless keystore");
+                        let secret = parse_privkey(&std::fs::read_to_string(path)?)?;
+                        store.import(&name, &secret, &read_password(true)?)?
+                    };
+                    println!("{}", account);
+                }
+                AccountCommand::Export {
+                    name,
+                    output,
+                    keystore,
+                } => {
+                    let content = if keystore {
+                        serde_json::to_string_pretty(&store.keystore(&name)?)?
+                    } else {
+                        let secret = store.export(&name, &read_password(false)?)?;
+                        format!("0x{}", hex_string(&secret))
+                    };
+                    match output {
+                        Some(path) => {
+                            std::fs::write(&path, content)?;
+                            println!("Exported {} to {}", name, path.display());
+                        }
+                        None => println!("{}", content),
+                    }
+                }
+                AccountCommand::Fund { target, amount } => {
+                    let recipient = match Address::decode(&target) {
+                        Ok(address) => address.script().clone(),
+                        Err(_) => store.get(&target)?.lock_script(),
+                    };
+                    let shannons = amount
+                        .checked_mul(ONE_CKB)
+                        .ok_or_else(|| anyhow!("Amount of {} CKB is too large", amount))?;
+                    let tx_hash = Faucet::new(&environment)?.fund(recipient, shannons)?;
+                    println!(
+                        "Sent {} CKB to {} in transaction {:#x}",
+                        amount, target, tx_hash
+                    );
+                }
+                AccountCommand::Remove { name, yes } => {
+                    let account = store.get(&name)?;
+                    if !yes && !confirm(&format!("Remove account {}?", account.name))? {
+                        return Ok(());
+                    }
+                    store.remove(&name)?;
+                    println!("Removed account {}", name);
+                }
+            }
+        }
+        TrampolineCommand::Tx { command } => {
+            let project = TrampolineProject::from(project?);
+            let environment = project.environment(&opts.env)?;
+            match command {
+                TxCommand::Sign {
+                    file,
+                    account,
+                    output,
+                    multisig_keys,
+                    threshold,
+                    require_first_n,
+                } => {
+                    let tx: Transaction = serde_json::from_str(&std::fs::read_to_string(&file)?)?;
+                    let tx = packed::Transaction::from(tx).into_view();
+
+                    let store = AccountStore::for_environment(&project, &environment);
+                    let secret = store.export(&account, &read_password(false)?)?;
+                    let mut signer = Signer::new(&secret)?;
+                    if !multisig_keys.is_empty() {
+                        let lock_args = multisig_keys
+                            .iter()
+                            .map(|key| {
+                                <[u8; 20]>::try_from(parse_hex(key)?.as_slice())
+                                    .map_err(|_| anyhow!("Lock arg {} is not 20 bytes", key))
+                            })
+                            .collect::<Result<Vec<_>>>()?;
+                        signer = signer.multisig(MultisigConfig::new(
+                            require_first_n,
+                            threshold,
+                            lock_args,
+                        )?);
+                    }
+
+                    // Input locks come from the chain, so the cells must already exist there
+                    let rpc = BlockingRpcClient::new(RpcClient::new(&environment.ckb_rpc))?;
+                    let signed = signer.sign(&tx, &input_locks(&rpc, &tx)?)?;
+                    let path = output.unwrap_or(file);
+                    std::fs::write(
+                        &path,
+                        serde_json::to_string_pretty(&Transaction::from(signed.data()))?,
+                    )?;
+                    let tx_hash: H256 = signed.hash().unpack();
+                    println!(
+                        "Signed transaction {:#x} with {}, saved to {}",
+                        tx_hash,
+                        account,
+                        path.display()
+                    );
+                }
+            }
+        }
+        TrampolineCommand::Generate {
+            pattern,
+            params,
+            output,
+        } => {
+            let project = TrampolineProject::from(project?);
+            let environment = project.environment(&opts.env)?;
+            let params = params.into_iter().collect();
+            let tx =
+                Pattern::load(&project, &pattern, &params)?.generate(&project, &environment)?;
+            let content = serde_json::to_string_pretty(&Transaction::from(tx.data()))?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, content)?;
+                    let tx_hash: H256 = tx.hash().unpack();
+                    println!(
+                        "Generated transaction {:#x} from {}, saved to {}",
+                        tx_hash,
+                        pattern,
+                        path.display()
+                    );
+                }
+                None => println!("{}", content),
+            }
+        }
+        TrampolineCommand::Script { command } => {
+            let mut project = TrampolineProject::from(project?);
+            match command {
+                ScriptCommand::New { name } => {
+                    let script = Script::new(&project, &name)?;
+                    println!("Created script {} at {}", name, script.path.display());
+                }
+                ScriptCommand::Build { names, release } => {
+                    let scripts = if names.is_empty() {
+                        Script::list(&project)?
+                    } else {
+                        names
+                            .iter()
+                            .map(|name| Script::load(&project, name))
+                            .collect::<Result<Vec<_>, _>>()?
+                    };
+                    if scripts.is_empty() {
+                        return Err(anyhow!(
+                            "No scripts to build. Create one with `trampoline script new <name>`"
+                        ));
+                    }
+                    for binary in script::build(&project, &scripts, build_profile(release))? {
+                        println!("{}\n", binary);
+                    }
+                }
+                ScriptCommand::Deploy {
+                    name,
+                    type_id,
+                    release,
+                    account,
+                    timeout,
+                } => {
+                    let environment = project.environment(&opts.env)?;
+                    let signer = deployment_signer(&project, &environment, account)?;
+                    let script = Script::load(&project, &name)?;
+                    println!(
+                        "Deploying {}, waiting for the transaction to be committed",
+                        name
+                    );
+                    let deployed = Deployer::new(&project, &environment, signer)?
+                        .timeout(Duration::from_secs(timeout))
+                        .deploy(&script, build_profile(release), type_id)?;
+                    project.record_deployment(&name, &deployed)?;
+                    print_deployment(&name, &deployed);
+                }
+                ScriptCommand::Upgrade {
+                    name,
+                    release,
+                    rollback,
+                    to,
+                    account,
+                    timeout,
+                } => {
+                    let environment = project.environment(&opts.env)?;
+                    let deployed = project
+                        .deployed(&name)
+                        .cloned()
+                        .ok_or_else(|| ScriptError::NotDeployed(name.clone()))?;
+                    let signer = deployment_signer(&project, &environment, account)?;
+                    let upgraded = {
+                        let deployer = Deployer::new(&project, &environment, signer)?
+                            .timeout(Duration::from_secs(timeout));
+                        let version = if rollback {
+                            Some(deployed.history.last().ok_or_else(|| {
+                                ScriptError::UnknownVersion(format!("of {} to roll back to", name))
+                            })?)
+                        } else if let Some(to) = &to {
+                            let prefix = format!("0x{}", to.trim_start_matches("0x"));
+                            Some(
+                                deployed
+                                    .history
+                                    .iter()
+                                    .rev()
+                                    .find(|version| {
+                                        format!("{:#x}", version.data_hash).starts_with(&prefix)
+                                    })
+                                    .ok_or_else(|| ScriptError::UnknownVersion(to.clone()))?,
+                            )
+                        } else {
+                            None
+                        };
+                        let code = match version {
+                            Some(version) => deployer.historical_code(version)?,
+                            None => Script::load(&project, &name)?
+                                .code(&project, build_profile(release))?
+                                .into(),
+                        };
+                        println!(
+                            "Upgrading {}, waiting for the transaction to be committed",
+                            name
+                        );
+                        deployer.upgrade(&name, &deployed, code)?
+                    };
+                    project.record_deployment(&name, &upgraded)?;
+                    print_deployment(&name, &upgraded);
+                }
+                ScriptCommand::Test {
+                    release,
+                    no_build,
+                    cargo_args,
+                } => {
+                    let profile = build_profile(release);
+                    let scripts = Script::list(&project)?;
+                    if !no_build && !scripts.is_empty() {
+                        script::build(&project, &scripts, profile)?;
+                    }
+                    script::test(&project, profile, &cargo_args)?;
+                }
+            }
+        }
+        TrampolineCommand::Pattern { command } => {
+            let project = TrampolineProject::from(project?);
+            match command {
+                PatternCommand::Compile {
+                    pattern,
+                    name,
+                    params,
+                } => {
+                    let params = params.into_iter().collect();
+                    let name = name.unwrap_or_else(|| pattern.clone());
+                    let crate_dir = Pattern::load(&project, &pattern, &params)?
+                        .compile(&project, &pattern, &name)?;
+                    println!("Compiled {} into {}", pattern, crate_dir.display());
+                }
+            }
         }
     }
 
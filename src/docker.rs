@@ -1,7 +1,15 @@
-use crate::project::VirtualEnv;
+mod engine;
+mod runtime;
+
+pub use engine::EngineBackend;
+pub use runtime::{
+    backend_from_config, detect_runtime, ContainerRuntime, CustomRuntime, DockerRuntime,
+    PodmanRuntime,
+};
+
+use crate::project::{RuntimeConfig, VirtualEnv};
 use std::collections::HashMap;
 use std::fmt::Formatter;
-use std::io::Write;
 use std::marker::PhantomData;
 use std::process::Command;
 
@@ -15,6 +23,9 @@ use std::string::ToString;
 
 pub const DOCKER_BIN: &str = "docker";
 pub const IMAGE_NAME: &str = "iamm/trampoline-env:latest";
+pub const INDEXER_IMAGE_NAME: &str = "nervos/ckb-indexer:0.3.2";
+// Pinned so contracts build reproducibly with the same nightly toolchain
+pub const BUILDER_IMAGE_NAME: &str = "thewawar/ckb-capsule:2021-12-25";
 #[derive(Debug, Error)]
 pub enum DockerError {
     #[error(transparent)]
@@ -23,8 +34,22 @@ pub enum DockerError {
     Any(#[from] anyhow::Error),
     #[error("No image set")]
     NoImage,
+    #[error("No container name set")]
+    NoName,
+    #[error("Docker command `{command}` failed: {stderr}")]
+    CommandFailed { command: String, stderr: String },
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Docker Engine API returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("Malformed response from Docker Engine API: {0}")]
+    InvalidResponse(String),
+    #[error("Process in container {container} exited with code {code}")]
+    ExitCode { container: String, code: i64 },
+    #[error("No container runtime found. Install docker or podman, or set `engine` under [runtime] in trampoline-env.toml")]
+    NoRuntime,
 }
-type DockerResult<T> = std::result::Result<T, DockerError>;
+pub type DockerResult<T> = std::result::Result<T, DockerError>;
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone)]
 pub struct Port(usize);
@@ -102,34 +127,25 @@ pub struct DockerContainer<'a> {
     pub volumes: Vec<Volume<'a>>,
     pub env_vars: HashMap<String, String>,
     pub image: DockerImage,
+    pub network: Option<String>,
 }
 
 impl std::fmt::Display for DockerContainer<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let port_bindings_string = self
+        let image_string = self.image.reference();
+        // Collect parts individually so empty lists don't leave blank arguments behind
+        let mut parts = self
             .port_bindings
             .iter()
             .map(|port| format!("-p{}", port))
-            .collect::<Vec<String>>()
-            .join(" ");
-        let image_string = {
-            if let Some(tag) = self.image.tag.as_ref() {
-                format!("{}:{}", self.image.name, tag)
-            } else {
-                self.image.name.clone()
-            }
-        };
-        let volumes_string = self
-            .volumes
-            .iter()
-            .map(|vol| format!("-v{}", vol))
-            .collect::<Vec<String>>()
-            .join(" ");
-        write!(
-            f,
-            "{} --name {} {} {}",
-            port_bindings_string, self.name, volumes_string, image_string
-        )
+            .collect::<Vec<String>>();
+        parts.push(format!("--name {}", self.name));
+        if let Some(network) = self.network.as_ref() {
+            parts.push(format!("--network {}", network));
+        }
+        parts.extend(self.volumes.iter().map(|vol| format!("-v{}", vol)));
+        parts.push(image_string);
+        write!(f, "{}", parts.join(" "))
     }
 }
 
@@ -142,6 +158,32 @@ pub struct DockerImage {
     pub build_args: HashMap<String, String>,
 }
 
+impl DockerImage {
+    // Splits `name:tag`, leaving a registry port such as `localhost:5000/img` in the name
+    pub fn from_reference(reference: &str) -> Self {
+        let name_start = reference.rfind('/').map(|i| i + 1).unwrap_or(0);
+        match reference[name_start..].rfind(':') {
+            Some(i) => Self {
+                name: reference[..name_start + i].to_string(),
+                tag: Some(reference[name_start + i + 1..].to_string()),
+                ..Default::default()
+            },
+            None => Self {
+                name: reference.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    // `name:tag` as accepted by `docker run` and the Engine API
+    pub fn reference(&self) -> String {
+        match self.tag.as_ref() {
+            Some(tag) => format!("{}:{}", self.name, tag),
+            None => self.name.clone(),
+        }
+    }
+}
+
 impl std::fmt::Display for DockerImage {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut fmt_string = "".to_string();
@@ -158,19 +200,14 @@ impl std::fmt::Display for DockerImage {
     }
 }
 
+/// Command line of a docker image or container command, kept for callers that build
+/// their own commands. Trampoline itself goes through a [`DockerBackend`].
 #[derive(Debug, Default)]
 pub struct DockerCommand<C> {
     _docker: PhantomData<C>,
     pub command_string: Option<String>,
 }
 
-// impl<T> Into<String> for DockerCommand<T> {
-//     fn into(self) -> String {
-//         self.command_string.unwrap_or_default()
-//     }
-// }
-
-// Changed above to impl From
 impl<T> From<DockerCommand<T>> for String {
     fn from(command: DockerCommand<T>) -> String {
         command.command_string.unwrap_or_default()
@@ -178,19 +215,25 @@ impl<T> From<DockerCommand<T>> for String {
 }
 
 impl<T> DockerCommand<T> {
+    /// Spawns the command with whichever of docker or podman is installed.
     pub fn execute(&self, args: Option<Vec<String>>) -> DockerResult<()> {
+        let runtime = detect_runtime().ok_or(DockerError::NoRuntime)?;
+        self.execute_with(runtime.as_ref(), args)
+    }
+
+    pub fn execute_with(
+        &self,
+        runtime: &dyn ContainerRuntime,
+        args: Option<Vec<String>>,
+    ) -> DockerResult<()> {
         if let Some(cmd_str) = &self.command_string {
-            let mut cmd = Command::new("docker");
+            let mut cmd = Command::new(runtime.binary());
             cmd_str.split(' ').for_each(|arg| {
                 cmd.arg(arg);
             });
             if let Some(args) = args {
                 cmd.args(args);
-                //println!("{}{}", cmd_str, args.join(" "));
             }
-            //println!("{}", cmd_str);
-            //cmd.arg(cmd_str);
-            //println!("{}", cmd_str);
             cmd.stdout(Stdio::null())
                 .stderr(Stdio::null())
                 .stdin(Stdio::null())
@@ -214,7 +257,7 @@ impl DockerCommand<DockerImage> {
             .collect::<Vec<String>>()
             .join(" ");
 
-        if !(&flags).is_empty() {
+        if !flags.is_empty() {
             format!("image {} {} {}", command_string, flags_string, image)
         } else {
             format!("image {} {}", command_string, image)
@@ -259,7 +302,7 @@ impl DockerCommand<DockerContainer<'_>> {
             .collect::<Vec<String>>()
             .join(" ");
 
-        if !(&flags).is_empty() {
+        if !flags.is_empty() {
             format!(
                 "container {} {} {}",
                 command_string, flags_string, container
@@ -297,27 +340,395 @@ impl DockerCommand<DockerContainer<'_>> {
         todo!()
     }
 
-    pub fn start(_container: &DockerContainer) -> DockerResult<()> {
-        todo!()
+    pub fn start(container: &DockerContainer) -> DockerResult<()> {
+        backend_from_config(None)?.start(&container.name)
     }
 
-    pub fn stop(_container: &DockerContainer) -> DockerResult<()> {
-        todo!()
+    pub fn stop(container: &DockerContainer) -> DockerResult<()> {
+        backend_from_config(None)?.stop(&container.name)
     }
 
-    pub fn pause(_container: &DockerContainer) -> DockerResult<()> {
-        todo!()
+    pub fn pause(container: &DockerContainer) -> DockerResult<()> {
+        Self::by_name("pause", container).execute(None)
     }
 
-    pub fn unpause(_container: &DockerContainer) -> DockerResult<()> {
-        todo!()
+    pub fn unpause(container: &DockerContainer) -> DockerResult<()> {
+        Self::by_name("unpause", container).execute(None)
     }
 
-    pub fn restart(_container: &DockerContainer) -> DockerResult<()> {
-        todo!()
+    pub fn restart(container: &DockerContainer) -> DockerResult<()> {
+        backend_from_config(None)?.restart(&container.name)
+    }
+
+    fn by_name(command_string: &str, container: &DockerContainer) -> Self {
+        DockerCommand {
+            command_string: Some(format!("container {} {}", command_string, container.name)),
+            _docker: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContainerStatus {
+    pub name: String,
+    pub state: String,
+    pub running: bool,
+    pub ports: Vec<String>,
+    pub mounts: Vec<String>,
+}
+
+impl ContainerStatus {
+    // Parses the output of `docker container inspect` for a single container
+    pub fn from_inspect(inspect_output: &str) -> DockerResult<Self> {
+        let parsed: serde_json::Value = serde_json::from_str(inspect_output)?;
+        let info = parsed.get(0).ok_or_else(|| {
+            DockerError::InvalidResponse("empty output from docker container inspect".to_string())
+        })?;
+        Ok(Self::from_inspect_value(info))
+    }
+
+    // Parses a single container object, as returned by `GET /containers/{id}/json`
+    pub fn from_inspect_value(info: &serde_json::Value) -> Self {
+        let name = info["Name"]
+            .as_str()
+            .unwrap_or_default()
+            .trim_start_matches('/')
+            .to_string();
+        let state = info["State"]["Status"]
+            .as_str()
+            .unwrap_or("unknown")
+            .to_string();
+        let running = info["State"]["Running"].as_bool().unwrap_or(false);
+
+        let mut ports = vec![];
+        if let Some(bindings) = info["NetworkSettings"]["Ports"].as_object() {
+            for (container_port, host_bindings) in bindings {
+                for binding in host_bindings.as_array().into_iter().flatten() {
+                    ports.push(format!(
+                        "{}:{}->{}",
+                        binding["HostIp"].as_str().unwrap_or_default(),
+                        binding["HostPort"].as_str().unwrap_or_default(),
+                        container_port
+                    ));
+                }
+            }
+        }
+
+        let mounts = info["Mounts"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|mount| {
+                format!(
+                    "{}:{}",
+                    mount["Source"].as_str().unwrap_or_default(),
+                    mount["Destination"].as_str().unwrap_or_default()
+                )
+            })
+            .collect();
+
+        Self {
+            name,
+            state,
+            running,
+            ports,
+            mounts,
+        }
     }
 }
 
+impl std::fmt::Display for ContainerStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Container: {} ({})", self.name, self.state)?;
+        if !self.ports.is_empty() {
+            writeln!(f, "Ports: {}", self.ports.join(", "))?;
+        }
+        for mount in &self.mounts {
+            writeln!(f, "Data: {}", mount)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    pub follow: bool,
+    // A UNIX timestamp or a duration such as `30s`, `10m`, `2h` or `1d`
+    pub since: Option<String>,
+    pub tail: Option<usize>,
+}
+
+/// Operations trampoline needs from a container engine. Implemented by
+/// [`CliBackend`], which drives the docker binary, and [`EngineBackend`],
+/// which talks to the Docker Engine API directly.
+pub trait DockerBackend {
+    /// Creates a bridge network. Succeeds if it already exists.
+    fn create_network(&self, name: &str) -> DockerResult<()>;
+    /// Removes a network. Succeeds if it does not exist.
+    fn remove_network(&self, name: &str) -> DockerResult<()>;
+    /// Creates and starts a container, returning its ID. When `detach` is
+    /// false, streams the container output and waits for it to exit.
+    fn run(
+        &self,
+        container: &DockerContainer,
+        args: &[String],
+        detach: bool,
+    ) -> DockerResult<String>;
+    fn start(&self, name: &str) -> DockerResult<()>;
+    fn stop(&self, name: &str) -> DockerResult<()>;
+    fn restart(&self, name: &str) -> DockerResult<()>;
+    /// Force-removes a container, stopping it first if needed.
+    fn remove(&self, name: &str) -> DockerResult<()>;
+    /// Returns `None` when no container with the given name exists.
+    fn inspect(&self, name: &str) -> DockerResult<Option<ContainerStatus>>;
+    /// Lists the command line of every process running in the container.
+    fn processes(&self, name: &str) -> DockerResult<Vec<String>>;
+    /// Runs a command inside a running container. When `detach` is false,
+    /// streams its output and fails if it exits with a non-zero code.
+    fn exec(&self, name: &str, args: &[&str], detach: bool) -> DockerResult<()>;
+    /// Writes the container output to stdout and stderr.
+    fn logs(&self, name: &str, options: &LogOptions) -> DockerResult<()>;
+    /// Returns the last `tail` lines of container output, stdout and stderr combined.
+    fn recent_logs(&self, name: &str, tail: usize) -> DockerResult<String>;
+    /// Builds an image from the Dockerfile in `context` and tags it.
+    fn build_image(&self, context: &Path, tag: &str) -> DockerResult<()>;
+}
+
+#[derive(Debug)]
+pub struct CliBackend {
+    runtime: Box<dyn ContainerRuntime>,
+}
+
+impl Default for CliBackend {
+    fn default() -> Self {
+        Self::new(Box::new(DockerRuntime))
+    }
+}
+
+impl CliBackend {
+    pub fn new(runtime: Box<dyn ContainerRuntime>) -> Self {
+        Self { runtime }
+    }
+
+    pub fn runtime(&self) -> &dyn ContainerRuntime {
+        self.runtime.as_ref()
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(self.runtime.binary());
+        cmd.args(args).stdin(Stdio::null());
+        cmd
+    }
+
+    // Waits for the command and returns its stdout, or its stderr as an error
+    fn output(&self, args: &[&str]) -> DockerResult<String> {
+        let output = self.command(args).output()?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        } else {
+            Err(DockerError::CommandFailed {
+                command: args.join(" "),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            })
+        }
+    }
+
+    // Waits for the command with its output attached to the terminal
+    fn attached(&self, args: &[&str]) -> DockerResult<()> {
+        let status = self
+            .command(args)
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(DockerError::CommandFailed {
+                command: args.join(" "),
+                stderr: format!("exited with {}", status),
+            })
+        }
+    }
+
+    fn run_args(
+        &self,
+        container: &DockerContainer,
+        args: &[String],
+        detach: bool,
+    ) -> DockerResult<Vec<String>> {
+        let mut run_args = vec!["container".to_string(), "run".to_string()];
+        if detach {
+            run_args.push("--detach".into());
+        }
+        run_args.push("--name".into());
+        run_args.push(container.name.clone());
+        if let Some(network) = container.network.as_ref() {
+            run_args.push("--network".into());
+            run_args.push(network.clone());
+        }
+        for port in &container.port_bindings {
+            run_args.push(format!("-p{}", port));
+        }
+        for volume in &container.volumes {
+            let host = volume.host.canonicalize()?;
+            let mut mount = format!("{}:{}", host.display(), volume.container.display());
+            if let Some(options) = self.runtime.volume_options() {
+                mount.push(':');
+                mount.push_str(options);
+            }
+            run_args.push("-v".into());
+            run_args.push(mount);
+        }
+        for (key, val) in &container.env_vars {
+            run_args.push("-e".into());
+            run_args.push(format!("{}={}", key, val));
+        }
+        run_args.push(self.image_reference(&container.image));
+        run_args.extend(args.iter().cloned());
+        Ok(run_args)
+    }
+
+    // Locally built images resolve by their short name; anything that will be
+    // pulled is qualified the way the runtime expects.
+    fn image_reference(&self, image: &DockerImage) -> String {
+        let reference = image.reference();
+        if self
+            .output(&["image", "inspect", reference.as_str()])
+            .is_ok()
+        {
+            reference
+        } else {
+            self.runtime.qualify_image(&reference)
+        }
+    }
+}
+
+impl DockerBackend for CliBackend {
+    fn create_network(&self, name: &str) -> DockerResult<()> {
+        match self.output(&["network", "create", name]) {
+            Err(DockerError::CommandFailed { stderr, .. }) if stderr.contains("already exists") => {
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn remove_network(&self, name: &str) -> DockerResult<()> {
+        match self.output(&["network", "rm", name]) {
+            Err(DockerError::CommandFailed { stderr, .. }) if stderr.contains("not found") => {
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn run(
+        &self,
+        container: &DockerContainer,
+        args: &[String],
+        detach: bool,
+    ) -> DockerResult<String> {
+        let run_args = self.run_args(container, args, detach)?;
+        let run_args = run_args.iter().map(String::as_str).collect::<Vec<&str>>();
+        if detach {
+            // `docker run --detach` prints the ID of the new container
+            self.output(&run_args)
+        } else {
+            self.attached(&run_args)?;
+            Ok(container.name.clone())
+        }
+    }
+
+    fn start(&self, name: &str) -> DockerResult<()> {
+        self.output(&["container", "start", name]).map(|_| ())
+    }
+
+    fn stop(&self, name: &str) -> DockerResult<()> {
+        self.output(&["container", "stop", name]).map(|_| ())
+    }
+
+    fn restart(&self, name: &str) -> DockerResult<()> {
+        self.output(&["container", "restart", name]).map(|_| ())
+    }
+
+    fn remove(&self, name: &str) -> DockerResult<()> {
+        self.output(&["container", "rm", "--force", name])
+            .map(|_| ())
+    }
+
+    fn inspect(&self, name: &str) -> DockerResult<Option<ContainerStatus>> {
+        match self.output(&["container", "inspect", name]) {
+            Ok(output) => Ok(Some(ContainerStatus::from_inspect(&output)?)),
+            // docker says "No such container", podman "no such container"
+            Err(DockerError::CommandFailed { stderr, .. })
+                if stderr.to_lowercase().contains("no such") =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn processes(&self, name: &str) -> DockerResult<Vec<String>> {
+        let mut args = vec!["top", name];
+        args.extend(self.runtime.top_args());
+        let output = self.output(&args)?;
+        Ok(output
+            .lines()
+            .skip(1)
+            .map(|line| line.trim().to_string())
+            .collect())
+    }
+
+    fn exec(&self, name: &str, args: &[&str], detach: bool) -> DockerResult<()> {
+        let mut exec_args = vec!["exec"];
+        if detach {
+            exec_args.push("--detach");
+        }
+        exec_args.push(name);
+        exec_args.extend_from_slice(args);
+        if detach {
+            self.output(&exec_args).map(|_| ())
+        } else {
+            self.attached(&exec_args)
+        }
+    }
+
+    fn logs(&self, name: &str, options: &LogOptions) -> DockerResult<()> {
+        let tail = options.tail.map(|tail| tail.to_string());
+        let mut args = vec!["logs"];
+        if options.follow {
+            args.push("--follow");
+        }
+        if let Some(since) = options.since.as_ref() {
+            args.extend_from_slice(&["--since", since.as_str()]);
+        }
+        if let Some(tail) = tail.as_ref() {
+            args.extend_from_slice(&["--tail", tail.as_str()]);
+        }
+        args.push(name);
+        self.attached(&args)
+    }
+
+    fn recent_logs(&self, name: &str, tail: usize) -> DockerResult<String> {
+        let tail = tail.to_string();
+        let output = self
+            .command(&["logs", "--tail", tail.as_str(), name])
+            .output()?;
+        // ckb logs to stdout and ckb-indexer to stderr, so keep both
+        let mut logs = String::from_utf8_lossy(&output.stdout).to_string();
+        logs.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(logs)
+    }
+
+    fn build_image(&self, context: &Path, tag: &str) -> DockerResult<()> {
+        let context = context.to_string_lossy();
+        self.attached(&["build", context.as_ref(), "-t", tag])
+    }
+}
+
+/// Runs the project image with the services added to it, through the backend
+/// selected by `runtime` (or detected when unset).
 #[derive(Debug, Default)]
 pub struct Docker {
     name: Option<String>,
@@ -325,6 +736,7 @@ pub struct Docker {
     env_vars: HashMap<String, String>,
     volumes: HashMap<VolumePath, VolumePath>,
     virtuals: Vec<VirtualEnv>,
+    runtime: Option<RuntimeConfig>,
 }
 
 impl Docker {
@@ -335,6 +747,7 @@ impl Docker {
             local_binding,
             container_mount,
             host_port,
+            spec: _,
         } = env.clone();
         self.port_bindings
             .push((host_port.into(), container_port.into()));
@@ -346,20 +759,28 @@ impl Docker {
         Ok(self)
     }
 
+    /// The `[runtime]` section whose engine runs the commands.
+    pub fn runtime(mut self, runtime: RuntimeConfig) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    fn backend(&self) -> DockerResult<Box<dyn DockerBackend>> {
+        backend_from_config(self.runtime.as_ref())
+    }
+
     pub fn build(&self) -> DockerResult<()> {
-        let mut cmd = Command::new(DOCKER_BIN);
-        cmd.arg("build");
-        cmd.arg(".");
-        cmd.arg("-t");
-        cmd.arg(IMAGE_NAME);
-        let output = cmd
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .output()?;
-        std::io::stdout().write_all(&output.stdout)?;
-        std::io::stdout().write_all(&output.stderr)?;
+        self.build_in(Path::new("."))
+    }
 
-        Ok(())
+    // Builds the project image from the Dockerfile in `context`
+    pub fn build_in(&self, context: &Path) -> DockerResult<()> {
+        let image = self
+            .runtime
+            .as_ref()
+            .map(|runtime| runtime.image())
+            .unwrap_or(IMAGE_NAME);
+        self.backend()?.build_image(context, image)
     }
 
     pub fn env_var(mut self, key: String, val: String) -> DockerResult<Self> {
@@ -373,72 +794,62 @@ impl Docker {
     }
 
     pub fn exec(container_name: &str, exec_args: Vec<&str>, _work_dir: &str) -> DockerResult<()> {
-        let mut cmd = Command::new(DOCKER_BIN);
-        cmd.args(&["exec", "-d", container_name, "bash", "-c"]);
-
         let args_string = exec_args.join(" ");
-        let args_string = args_string;
-        println!("Args string: {}", args_string);
-        cmd.arg(args_string.as_str());
-
-        println!("{:?}", cmd);
-        cmd.stdout(Stdio::null())
-            .stderr(Stdio::inherit())
-            .stdin(Stdio::null())
-            .spawn()?;
-
-        Ok(())
+        backend_from_config(None)?.exec(container_name, &["bash", "-c", &args_string], true)
     }
 
     pub fn restart(&self) -> DockerResult<()> {
-        let mut cmd = Command::new(DOCKER_BIN);
-        cmd.args(&["restart", self.name.as_ref().unwrap().as_str()]);
-
-        let _child = cmd.stdout(Stdio::null()).stderr(Stdio::inherit()).spawn()?;
-
-        Ok(())
+        let name = self.name.as_ref().ok_or(DockerError::NoName)?;
+        self.backend()?.restart(name)
     }
+
     pub fn run(
         &self,
         exec_args: Option<Vec<String>>,
         name_mod: Option<&str>,
         additional_ports: Vec<(Port, Port)>,
     ) -> DockerResult<()> {
-        let mut cmd = Command::new(DOCKER_BIN);
-        cmd.args(&["run", "--rm", "-d", "-eCKB_CHAIN:dev"]);
-        let mut container_name = None;
-
-        if let Some(name) = self.name.as_ref() {
-            if let Some(name_mod) = name_mod {
-                let display_name = format!("{}-{}", &name, name_mod);
-                container_name = Some(display_name);
-                cmd.arg("--name").arg(container_name.as_ref().unwrap());
-            } else {
-                container_name = Some(name.clone());
-                cmd.arg("--name").arg(name);
-            }
-        }
-        self.port_bindings.iter().for_each(|bind| {
-            cmd.arg(format!("-p{}:{}", bind.0, bind.1).as_str());
-        });
-        additional_ports.iter().for_each(|bind| {
-            cmd.arg(format!("-p{}:{}", bind.0, bind.1).as_str());
-        });
-        self.volumes.iter().for_each(|bind| {
-            cmd.arg(format!("-v{}:{}", bind.0, bind.1).as_str());
-        });
-
-        cmd.arg(IMAGE_NAME);
-        if exec_args.is_some() {
-            cmd.args(exec_args.unwrap().as_slice());
-        }
-
-        let _child = cmd.stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+        let name = self.name.as_ref().ok_or(DockerError::NoName)?;
+        let container_name = match name_mod {
+            Some(name_mod) => format!("{}-{}", name, name_mod),
+            None => name.clone(),
+        };
+        let volumes = self
+            .volumes
+            .iter()
+            .map(|(host, container)| (PathBuf::from(&host.0), PathBuf::from(&container.0)))
+            .collect::<Vec<_>>();
+        let mut env_vars = self.env_vars.clone();
+        env_vars.insert("CKB_CHAIN".to_string(), "dev".to_string());
+        let image = self
+            .runtime
+            .as_ref()
+            .map(|runtime| runtime.image())
+            .unwrap_or(IMAGE_NAME);
+        let container = DockerContainer {
+            name: container_name.clone(),
+            port_bindings: self
+                .port_bindings
+                .iter()
+                .chain(&additional_ports)
+                .map(|(host, container)| DockerPort {
+                    host: host.0,
+                    container: container.0,
+                })
+                .collect(),
+            volumes: volumes
+                .iter()
+                .map(|(host, container)| Volume { host, container })
+                .collect(),
+            env_vars,
+            image: DockerImage::from_reference(image),
+            network: None,
+        };
+        self.backend()?
+            .run(&container, &exec_args.unwrap_or_default(), true)?;
 
         println!("Successfully started network!\n");
-        if container_name.is_some() {
-            println!("Running in container: {}", container_name.unwrap());
-        }
+        println!("Running in container: {}", container_name);
         self.virtuals.iter().for_each(|e| println!("{}", e));
 
         Ok(())
@@ -487,4 +898,34 @@ mod tests {
             "image rm trampoline"
         );
     }
+
+    #[test]
+    fn test_container_status_from_inspect() {
+        let inspect = r#"[{
+            "Name": "/proj-node",
+            "State": { "Status": "running", "Running": true },
+            "NetworkSettings": {
+                "Ports": { "8114/tcp": [{ "HostIp": "0.0.0.0", "HostPort": "8114" }], "8115/tcp": null }
+            },
+            "Mounts": [{ "Source": "/home/dev/proj/.trampoline/network", "Destination": "/var/lib/ckb" }]
+        }]"#;
+        let status = ContainerStatus::from_inspect(inspect).unwrap();
+        assert_eq!(status.name, "proj-node");
+        assert!(status.running);
+        assert_eq!(status.ports, vec!["0.0.0.0:8114->8114/tcp".to_string()]);
+        assert_eq!(
+            status.mounts,
+            vec!["/home/dev/proj/.trampoline/network:/var/lib/ckb".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_image_from_reference() {
+        let image = DockerImage::from_reference("localhost:5000/nervos/ckb-indexer:0.3.2");
+        assert_eq!(image.name, "localhost:5000/nervos/ckb-indexer");
+        assert_eq!(image.tag.as_deref(), Some("0.3.2"));
+        let image = DockerImage::from_reference("localhost:5000/trampoline");
+        assert_eq!(image.name, "localhost:5000/trampoline");
+        assert_eq!(image.tag, None);
+    }
 }
@@ -0,0 +1,699 @@
+use super::{
+    CliBackend, ContainerStatus, DockerBackend, DockerContainer, DockerError, DockerImage,
+    DockerResult, LogOptions,
+};
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// Talks to the Docker Engine HTTP API over its unix socket.
+#[derive(Debug, Clone)]
+pub struct EngineBackend {
+    socket_path: PathBuf,
+}
+
+impl Default for EngineBackend {
+    fn default() -> Self {
+        Self::new(DEFAULT_DOCKER_SOCKET)
+    }
+}
+
+impl EngineBackend {
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        Self {
+            socket_path: socket_path.into(),
+        }
+    }
+
+    /// Uses `DOCKER_HOST` when it points at a unix socket, and the default socket otherwise.
+    pub fn from_env() -> Self {
+        match std::env::var("DOCKER_HOST") {
+            Ok(host) if host.starts_with("unix://") => {
+                Self::new(host.trim_start_matches("unix://"))
+            }
+            _ => Self::default(),
+        }
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.request("GET", "/_ping", None)
+            .map(|response| response.status == 200)
+            .unwrap_or(false)
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&Value>,
+    ) -> DockerResult<EngineResponse> {
+        let mut stream = UnixStream::connect(&self.socket_path)?;
+        let body = match body {
+            Some(body) => serde_json::to_vec(body)?,
+            None => vec![],
+        };
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n",
+            method, path
+        );
+        if method == "POST" || !body.is_empty() {
+            head.push_str("Content-Type: application/json\r\n");
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&body)?;
+        stream.flush()?;
+        EngineResponse::read(BufReader::new(stream))
+    }
+
+    // Like `request`, but turns error statuses into `DockerError::Api`
+    fn call(&self, method: &str, path: &str, body: Option<&Value>) -> DockerResult<EngineResponse> {
+        let response = self.request(method, path, body)?;
+        if response.status >= 400 {
+            Err(response.into_error())
+        } else {
+            Ok(response)
+        }
+    }
+
+    fn pull(&self, image: &DockerImage) -> DockerResult<()> {
+        let path = format!(
+            "/images/create?fromImage={}&tag={}",
+            encode(&image.name),
+            encode(image.tag.as_deref().unwrap_or("latest"))
+        );
+        // Progress is streamed as JSON lines, and failures show up as an `error` line
+        let progress = self.call("POST", &path, None)?.bytes()?;
+        for line in String::from_utf8_lossy(&progress).lines() {
+            if let Ok(event) = serde_json::from_str::<Value>(line) {
+                if let Some(error) = event["error"].as_str() {
+                    return Err(DockerError::Api {
+                        status: 500,
+                        message: error.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DockerBackend for EngineBackend {
+    fn create_network(&self, name: &str) -> DockerResult<()> {
+        let body = json!({ "Name": name, "CheckDuplicate": true });
+        match self.call("POST", "/networks/create", Some(&body)) {
+            Err(DockerError::Api { status: 409, .. }) => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn remove_network(&self, name: &str) -> DockerResult<()> {
+        match self.call("DELETE", &format!("/networks/{}", encode(name)), None) {
+            Err(DockerError::Api { status: 404, .. }) => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn run(
+        &self,
+        container: &DockerContainer,
+        args: &[String],
+        detach: bool,
+    ) -> DockerResult<String> {
+        let path = format!("/containers/create?name={}", encode(&container.name));
+        let body = create_body(container, args)?;
+        let response = match self.call("POST", &path, Some(&body)) {
+            Err(DockerError::Api {
+                status: 404,
+                message,
+            }) if message.contains("No such image") => {
+                self.pull(&container.image)?;
+                self.call("POST", &path, Some(&body))?
+            }
+            result => result?,
+        };
+        let id = response.json()?["Id"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| DockerError::InvalidResponse("missing container Id".to_string()))?;
+
+        self.call("POST", &format!("/containers/{}/start", id), None)?;
+        if !detach {
+            let follow = LogOptions {
+                follow: true,
+                ..Default::default()
+            };
+            self.logs(&id, &follow)?;
+            let exit = self
+                .call("POST", &format!("/containers/{}/wait", id), None)?
+                .json()?;
+            let code = exit["StatusCode"].as_i64().unwrap_or_default();
+            if code != 0 {
+                return Err(DockerError::ExitCode {
+                    container: container.name.clone(),
+                    code,
+                });
+            }
+        }
+        Ok(id)
+    }
+
+    fn start(&self, name: &str) -> DockerResult<()> {
+        self.call("POST", &format!("/containers/{}/start", encode(name)), None)
+            .map(|_| ())
+    }
+
+    fn stop(&self, name: &str) -> DockerResult<()> {
+        self.call("POST", &format!("/containers/{}/stop", encode(name)), None)
+            .map(|_| ())
+    }
+
+    fn restart(&self, name: &str) -> DockerResult<()> {
+        self.call(
+            "POST",
+            &format!("/containers/{}/restart", encode(name)),
+            None,
+        )
+        .map(|_| ())
+    }
+
+    fn remove(&self, name: &str) -> DockerResult<()> {
+        self.call(
+            "DELETE",
+            &format!("/containers/{}?force=true", encode(name)),
+            None,
+        )
+        .map(|_| ())
+    }
+
+    fn inspect(&self, name: &str) -> DockerResult<Option<ContainerStatus>> {
+        match self.call("GET", &format!("/containers/{}/json", encode(name)), None) {
+            Ok(response) => Ok(Some(ContainerStatus::from_inspect_value(&response.json()?))),
+            Err(DockerError::Api { status: 404, .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn processes(&self, name: &str) -> DockerResult<Vec<String>> {
+        let top = self
+            .call(
+                "GET",
+                &format!(
+                    "/containers/{}/top?ps_args={}",
+                    encode(name),
+                    encode("-o args")
+                ),
+                None,
+            )?
+            .json()?;
+        Ok(top["Processes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|process| process.as_array()?.last()?.as_str().map(String::from))
+            .collect())
+    }
+
+    fn exec(&self, name: &str, args: &[&str], detach: bool) -> DockerResult<()> {
+        let body = json!({
+            "Cmd": args,
+            "AttachStdout": !detach,
+            "AttachStderr": !detach,
+        });
+        let exec = self
+            .call(
+                "POST",
+                &format!("/containers/{}/exec", encode(name)),
+                Some(&body),
+            )?
+            .json()?;
+        let id = exec["Id"]
+            .as_str()
+            .ok_or_else(|| DockerError::InvalidResponse("missing exec Id".to_string()))?;
+
+        let start = json!({ "Detach": detach, "Tty": false });
+        let response = self.call("POST", &format!("/exec/{}/start", id), Some(&start))?;
+        if detach {
+            return Ok(());
+        }
+        demux(response.body, &mut io::stdout(), &mut io::stderr())?;
+
+        let result = self
+            .call("GET", &format!("/exec/{}/json", id), None)?
+            .json()?;
+        let code = result["ExitCode"].as_i64().unwrap_or_default();
+        if code != 0 {
+            return Err(DockerError::ExitCode {
+                container: name.to_string(),
+                code,
+            });
+        }
+        Ok(())
+    }
+
+    fn logs(&self, name: &str, options: &LogOptions) -> DockerResult<()> {
+        let mut path = format!("/containers/{}/logs?stdout=1&stderr=1", encode(name));
+        if options.follow {
+            path.push_str("&follow=1");
+        }
+        if let Some(since) = options.since.as_ref() {
+            path.push_str(&format!("&since={}", since_timestamp(since)?));
+        }
+        if let Some(tail) = options.tail {
+            path.push_str(&format!("&tail={}", tail));
+        }
+        let response = self.call("GET", &path, None)?;
+        demux(response.body, &mut io::stdout(), &mut io::stderr())?;
+        Ok(())
+    }
+
+    fn recent_logs(&self, name: &str, tail: usize) -> DockerResult<String> {
+        let path = format!(
+            "/containers/{}/logs?stdout=1&stderr=1&tail={}",
+            encode(name),
+            tail
+        );
+        let response = self.call("GET", &path, None)?;
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        demux(response.body, &mut stdout, &mut stderr)?;
+        stdout.extend(stderr);
+        Ok(String::from_utf8_lossy(&stdout).to_string())
+    }
+
+    // `POST /build` wants the context as a tar archive, which the CLI already knows how to make
+    fn build_image(&self, context: &Path, tag: &str) -> DockerResult<()> {
+        CliBackend::default().build_image(context, tag)
+    }
+}
+
+fn create_body(container: &DockerContainer, args: &[String]) -> DockerResult<Value> {
+    let mut exposed_ports = serde_json::Map::new();
+    let mut port_bindings = serde_json::Map::new();
+    for port in &container.port_bindings {
+        let key = format!("{}/tcp", port.container);
+        exposed_ports.insert(key.clone(), json!({}));
+        port_bindings.insert(key, json!([{ "HostPort": port.host.to_string() }]));
+    }
+    let binds = container
+        .volumes
+        .iter()
+        .map(|volume| {
+            Ok(format!(
+                "{}:{}",
+                volume.host.canonicalize()?.display(),
+                volume.container.display()
+            ))
+        })
+        .collect::<DockerResult<Vec<String>>>()?;
+    let env = container
+        .env_vars
+        .iter()
+        .map(|(key, val)| format!("{}={}", key, val))
+        .collect::<Vec<String>>();
+
+    let mut body = json!({
+        "Image": container.image.reference(),
+        "Env": env,
+        "ExposedPorts": exposed_ports,
+        "HostConfig": {
+            "PortBindings": port_bindings,
+            "Binds": binds,
+        },
+    });
+    if !args.is_empty() {
+        body["Cmd"] = json!(args);
+    }
+    if let Some(network) = container.network.as_ref() {
+        body["HostConfig"]["NetworkMode"] = json!(network);
+    }
+    Ok(body)
+}
+
+struct EngineResponse {
+    status: u16,
+    body: Box<dyn Read>,
+}
+
+impl EngineResponse {
+    fn read(mut reader: BufReader<UnixStream>) -> DockerResult<Self> {
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line)?;
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| {
+                DockerError::InvalidResponse(format!("bad status line {:?}", status_line.trim()))
+            })?;
+
+        let mut chunked = false;
+        let mut content_length = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                let value = value.trim();
+                if name.eq_ignore_ascii_case("transfer-encoding") {
+                    chunked = value.eq_ignore_ascii_case("chunked");
+                } else if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.parse::<u64>().ok();
+                }
+            }
+        }
+
+        // Streaming endpoints (logs, attached exec) have neither header and end at EOF
+        let body: Box<dyn Read> = if chunked {
+            Box::new(ChunkedReader::new(reader))
+        } else if let Some(len) = content_length {
+            Box::new(reader.take(len))
+        } else {
+            Box::new(reader)
+        };
+        Ok(Self { status, body })
+    }
+
+    fn bytes(mut self) -> DockerResult<Vec<u8>> {
+        let mut buf = vec![];
+        self.body.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn json(self) -> DockerResult<Value> {
+        let bytes = self.bytes()?;
+        if bytes.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    fn into_error(self) -> DockerError {
+        let status = self.status;
+        let message = match self.bytes() {
+            Ok(bytes) => serde_json::from_slice::<Value>(&bytes)
+                .ok()
+                .and_then(|body| body["message"].as_str().map(String::from))
+                .unwrap_or_else(|| String::from_utf8_lossy(&bytes).trim().to_string()),
+            Err(e) => e.to_string(),
+        };
+        DockerError::Api { status, message }
+    }
+}
+
+// Decodes an HTTP/1.1 chunked body as it arrives, so followed logs are printed live
+struct ChunkedReader<R> {
+    inner: R,
+    remaining: usize,
+    done: bool,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            let mut size_line = String::new();
+            if self.inner.read_line(&mut size_line)? == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+            let size = size_line.trim().split(';').next().unwrap_or_default();
+            let size = usize::from_str_radix(size, 16)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if size == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+            self.remaining = size;
+        }
+        let max = buf.len().min(self.remaining);
+        let read = self.inner.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read;
+        if self.remaining == 0 {
+            let mut crlf = [0u8; 2];
+            self.inner.read_exact(&mut crlf)?;
+        }
+        Ok(read)
+    }
+}
+
+// Containers without a TTY multiplex stdout and stderr into frames with an
+// 8 byte header: the stream type, three bytes of padding and a big-endian length.
+fn demux(
+    mut reader: impl Read,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> io::Result<()> {
+    let mut header = [0u8; 8];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let mut frame = vec![0u8; size];
+        reader.read_exact(&mut frame)?;
+        if header[0] == 2 {
+            stderr.write_all(&frame)?;
+            stderr.flush()?;
+        } else {
+            stdout.write_all(&frame)?;
+            stdout.flush()?;
+        }
+    }
+}
+
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// The Engine API only accepts UNIX timestamps, so durations such as `10m`
+// are converted relative to now. Plain numbers are passed through as-is.
+fn since_timestamp(since: &str) -> DockerResult<String> {
+    if since.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(since.to_string());
+    }
+    let invalid = || {
+        DockerError::Any(anyhow::anyhow!(
+            "Invalid --since value {}. Use a UNIX timestamp or a duration such as 30s, 10m, 2h or 1d",
+            since
+        ))
+    };
+    let unit = since.chars().last().ok_or_else(invalid)?;
+    let amount = since[..since.len() - unit.len_utf8()]
+        .parse::<u64>()
+        .map_err(|_| invalid())?;
+    let seconds = match unit {
+        's' => amount,
+        'm' => amount * 60,
+        'h' => amount * 60 * 60,
+        'd' => amount * 60 * 60 * 24,
+        _ => return Err(invalid()),
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| DockerError::Any(e.into()))?
+        .as_secs();
+    Ok(now.saturating_sub(seconds).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::DockerPort;
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    // Answers each incoming connection with the next canned response and
+    // hands back the raw requests it received.
+    fn fake_engine(
+        name: &str,
+        responses: Vec<String>,
+    ) -> (EngineBackend, thread::JoinHandle<Vec<String>>) {
+        let path =
+            std::env::temp_dir().join(format!("trampoline-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let handle = thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|response| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let request = read_request(&stream);
+                    stream.write_all(response.as_bytes()).unwrap();
+                    request
+                })
+                .collect()
+        });
+        (EngineBackend::new(path), handle)
+    }
+
+    fn read_request(stream: &UnixStream) -> String {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(len) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                content_length = len.trim().parse().unwrap();
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).unwrap();
+        request + &String::from_utf8(body).unwrap()
+    }
+
+    fn response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    #[test]
+    fn test_run_creates_and_starts_container() {
+        let (engine, server) = fake_engine(
+            "run",
+            vec![
+                response("201 Created", r#"{"Id":"abc123","Warnings":[]}"#),
+                response("204 No Content", ""),
+            ],
+        );
+        let container = DockerContainer {
+            name: "proj-node".to_string(),
+            port_bindings: vec![DockerPort {
+                host: 8114,
+                container: 8114,
+            }],
+            volumes: vec![],
+            env_vars: HashMap::new(),
+            image: DockerImage {
+                name: "trampoline".to_string(),
+                tag: Some("latest".to_string()),
+                ..Default::default()
+            },
+            network: Some("proj-network".to_string()),
+        };
+        let id = engine.run(&container, &["run".to_string()], true).unwrap();
+        assert_eq!(id, "abc123");
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /containers/create?name=proj-node HTTP/1.1"));
+        let body: Value =
+            serde_json::from_str(requests[0].split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["Image"], "trampoline:latest");
+        assert_eq!(body["Cmd"], json!(["run"]));
+        assert_eq!(body["HostConfig"]["NetworkMode"], "proj-network");
+        assert_eq!(
+            body["HostConfig"]["PortBindings"]["8114/tcp"][0]["HostPort"],
+            "8114"
+        );
+        assert!(requests[1].starts_with("POST /containers/abc123/start HTTP/1.1"));
+    }
+
+    #[test]
+    fn test_inspect_missing_container() {
+        let (engine, server) = fake_engine(
+            "inspect",
+            vec![response(
+                "404 Not Found",
+                r#"{"message":"No such container: proj-node"}"#,
+            )],
+        );
+        assert!(engine.inspect("proj-node").unwrap().is_none());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_api_error_is_typed() {
+        let (engine, server) = fake_engine(
+            "stop",
+            vec![response(
+                "500 Internal Server Error",
+                r#"{"message":"boom"}"#,
+            )],
+        );
+        match engine.stop("proj-node") {
+            Err(DockerError::Api { status, message }) => {
+                assert_eq!(status, 500);
+                assert_eq!(message, "boom");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_chunked_multiplexed_stream() {
+        let mut frames = vec![];
+        frames.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 6]);
+        frames.extend_from_slice(b"hello\n");
+        frames.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 4]);
+        frames.extend_from_slice(b"oops");
+        // Split the frames across two chunks, cutting through a header
+        let (first, second) = frames.split_at(10);
+        let mut chunked = vec![];
+        for chunk in [first, second] {
+            chunked.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            chunked.extend_from_slice(chunk);
+            chunked.extend_from_slice(b"\r\n");
+        }
+        chunked.extend_from_slice(b"0\r\n\r\n");
+
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        demux(
+            ChunkedReader::new(chunked.as_slice()),
+            &mut stdout,
+            &mut stderr,
+        )
+        .unwrap();
+        assert_eq!(stdout, b"hello\n");
+        assert_eq!(stderr, b"oops");
+    }
+}
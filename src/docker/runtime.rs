@@ -0,0 +1,155 @@
+use super::{CliBackend, DockerBackend, DockerError, DockerResult, EngineBackend, DOCKER_BIN};
+use crate::project::RuntimeConfig;
+
+use std::process::{Command, Stdio};
+
+pub const PODMAN_BIN: &str = "podman";
+
+/// A docker-compatible command line tool that [`CliBackend`] drives.
+pub trait ContainerRuntime: std::fmt::Debug {
+    fn binary(&self) -> &str;
+
+    /// Options appended to every bind mount, e.g. `Z` to relabel for SELinux.
+    fn volume_options(&self) -> Option<&str> {
+        None
+    }
+
+    /// Arguments for `top` that list only the command line of each process.
+    fn top_args(&self) -> Vec<&str> {
+        vec!["-o", "args"]
+    }
+
+    /// Turns a short image name into one the runtime can pull without prompting.
+    fn qualify_image(&self, image: &str) -> String {
+        image.to_string()
+    }
+
+    fn is_available(&self) -> bool {
+        Command::new(self.binary())
+            .arg("version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DockerRuntime;
+
+impl ContainerRuntime for DockerRuntime {
+    fn binary(&self) -> &str {
+        DOCKER_BIN
+    }
+}
+
+/// Podman, usually rootless. The indexer reaches the node by container name on
+/// the project network, which podman resolves through netavark's DNS server
+/// (or the dnsname CNI plugin on podman 3).
+#[derive(Debug, Clone, Default)]
+pub struct PodmanRuntime;
+
+impl ContainerRuntime for PodmanRuntime {
+    fn binary(&self) -> &str {
+        PODMAN_BIN
+    }
+
+    // Rootless podman on SELinux hosts denies access to unlabeled bind mounts
+    fn volume_options(&self) -> Option<&str> {
+        Some("Z")
+    }
+
+    // `podman top` takes format descriptors rather than ps options
+    fn top_args(&self) -> Vec<&str> {
+        vec!["args"]
+    }
+
+    // Podman refuses to guess the registry of short names when not running interactively
+    fn qualify_image(&self, image: &str) -> String {
+        let registry = image.split('/').next().unwrap_or_default();
+        let has_registry = image.contains('/')
+            && (registry.contains('.') || registry.contains(':') || registry == "localhost");
+        if has_registry {
+            image.to_string()
+        } else if image.contains('/') {
+            format!("docker.io/{}", image)
+        } else {
+            format!("docker.io/library/{}", image)
+        }
+    }
+}
+
+/// Any other binary that accepts docker's command line, e.g. `nerdctl`.
+#[derive(Debug, Clone)]
+pub struct CustomRuntime {
+    pub binary: String,
+}
+
+impl ContainerRuntime for CustomRuntime {
+    fn binary(&self) -> &str {
+        &self.binary
+    }
+}
+
+/// Returns the first of docker and podman that is installed.
+pub fn detect_runtime() -> Option<Box<dyn ContainerRuntime>> {
+    let candidates: Vec<Box<dyn ContainerRuntime>> =
+        vec![Box::new(DockerRuntime), Box::new(PodmanRuntime)];
+    candidates
+        .into_iter()
+        .find(|runtime| runtime.is_available())
+}
+
+/// Picks a backend from the `[runtime]` section of `trampoline-env.toml`.
+/// Without one, the Docker Engine socket is preferred, then whichever of
+/// docker or podman is installed.
+pub fn backend_from_config(config: Option<&RuntimeConfig>) -> DockerResult<Box<dyn DockerBackend>> {
+    let engine = config.and_then(|config| config.engine.as_deref());
+    match engine {
+        None | Some("auto") => {
+            let engine = EngineBackend::from_env();
+            if engine.is_available() {
+                return Ok(Box::new(engine));
+            }
+            let runtime = detect_runtime().ok_or(DockerError::NoRuntime)?;
+            Ok(Box::new(CliBackend::new(runtime)))
+        }
+        Some("docker") => {
+            let engine = EngineBackend::from_env();
+            if engine.is_available() {
+                Ok(Box::new(engine))
+            } else {
+                Ok(Box::new(CliBackend::new(Box::new(DockerRuntime))))
+            }
+        }
+        Some("podman") => Ok(Box::new(CliBackend::new(Box::new(PodmanRuntime)))),
+        Some(binary) => Ok(Box::new(CliBackend::new(Box::new(CustomRuntime {
+            binary: binary.to_string(),
+        })))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_podman_qualifies_short_names() {
+        let podman = PodmanRuntime;
+        assert_eq!(
+            podman.qualify_image("nervos/ckb-indexer:0.3.2"),
+            "docker.io/nervos/ckb-indexer:0.3.2"
+        );
+        assert_eq!(podman.qualify_image("ubuntu"), "docker.io/library/ubuntu");
+        assert_eq!(
+            podman.qualify_image("localhost/iamm/trampoline-env:latest"),
+            "localhost/iamm/trampoline-env:latest"
+        );
+        assert_eq!(
+            podman.qualify_image("quay.io/podman/stable"),
+            "quay.io/podman/stable"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::account::parse_privkey;
+use crate::address::sighash_script;
+use crate::indexer::{BlockingIndexerClient, IndexerClient, IndexerError};
+use crate::project::{ChainType, Environment};
+use crate::rpc::{BlockingRpcClient, RpcClient, RpcError};
+use crate::transaction::{GenesisInfo, Signer, TransactionBuilder, TransactionError};
+
+use ckb_jsonrpc_types::{BlockView, OutPoint, Transaction};
+use ckb_types::{bytes::Bytes, core::Capacity, packed, prelude::*, H256};
+use thiserror::Error;
+
+/// Key of the largest genesis cell in ckb's bundled dev spec.
+pub const DEV_FAUCET_PRIVKEY: &str =
+    "0xd00c06bfd800d27397002dca6fb0993d5ba6399b4238b2f29ee9deb97593d2bc";
+
+#[derive(Debug, Error)]
+pub enum FaucetError {
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    #[error(transparent)]
+    Indexer(#[from] IndexerError),
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+    #[error("The faucet only works on a local dev chain, but environment {0} is not one")]
+    NotDevChain(String),
+    #[error("Unexpected genesis block: {0}")]
+    InvalidGenesis(String),
+}
+
+pub type FaucetResult<T> = std::result::Result<T, FaucetError>;
+
+/// Sends CKB from the dev genesis account, signing with its well-known key.
+pub struct Faucet {
+    ckb: BlockingRpcClient,
+    indexer: BlockingIndexerClient,
+    signer: Signer,
+    lock: packed::Script,
+}
+
+impl Faucet {
+    pub fn new(environment: &Environment) -> FaucetResult<Self> {
+        if !environment.local || environment.chain != ChainType::Dev {
+            return Err(FaucetError::NotDevChain(environment.name.clone()));
+        }
+        let secret = parse_privkey(DEV_FAUCET_PRIVKEY).expect("valid dev key");
+        let signer = Signer::new(&secret)?;
+        Ok(Faucet {
+            ckb: BlockingRpcClient::new(RpcClient::new(&environment.ckb_rpc))?,
+            indexer: BlockingIndexerClient::new(IndexerClient::new(&environment.indexer_rpc))?,
+            lock: sighash_script(&signer.lock_arg()),
+            signer,
+        })
+    }
+
+    /// Transfers `capacity` shannons to `recipient` and returns the transaction hash.
+    pub fn fund(&self, recipient: packed::Script, capacity: u64) -> FaucetResult<H256> {
+        let genesis = self
+            .ckb
+            .get_block_by_number(0)?
+            .ok_or_else(|| FaucetError::InvalidGenesis("missing genesis block".into()))?;
+        let output = packed::CellOutput::new_builder()
+            .capacity(Capacity::shannons(capacity).pack())
+            .lock(recipient)
+            .build();
+        let mut builder = TransactionBuilder::new(&self.ckb, &self.indexer)
+            .genesis(GenesisInfo::from_block(&genesis)?)
+            .output(output, Bytes::new())
+            .change_lock(self.lock.clone());
+        // Without a running indexer only the untouched genesis cells can be found
+        if self.indexer.get_tip().is_ok() {
+            builder = builder.from_lock(self.lock.clone());
+        } else {
+            for out_point in self.genesis_cells(&genesis)? {
+                builder = builder.input(out_point);
+            }
+        }
+        let tx = builder.build()?;
+        // Every input is a faucet cell
+        let tx = self
+            .signer
+            .sign(&tx, &vec![self.lock.clone(); tx.inputs().len()])?;
+
+        Ok(self
+            .ckb
+            .send_transaction(&Transaction::from(tx.data()), None)?)
+    }
+
+    fn genesis_cells(&self, genesis: &BlockView) -> FaucetResult<Vec<packed::OutPoint>> {
+        let cellbase = genesis
+            .transactions
+            .first()
+            .ok_or_else(|| FaucetError::InvalidGenesis("missing cellbase".into()))?;
+        let mut cells = vec![];
+        for (index, output) in cellbase.inner.outputs.iter().enumerate() {
+            if packed::Script::from(output.lock.clone()) != self.lock {
+                continue;
+            }
+            let out_point = packed::OutPoint::new(cellbase.hash.pack(), index as u32);
+            let status = self
+                .ckb
+                .get_live_cell(&OutPoint::from(out_point.clone()), false)?;
+            if status.status == "live" {
+                cells.push(out_point);
+            }
+        }
+        Ok(cells)
+    }
+}
@@ -0,0 +1,430 @@
+use crate::project::{TrampolineProject, VirtualEnv};
+use crate::rpc::{RpcClient, RpcError};
+
+use ckb_jsonrpc_types::{
+    BlockNumber, Capacity, CellOutput, JsonBytes, OutPoint, Script, Uint32, Uint64,
+};
+use ckb_types::H256;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::VecDeque;
+use thiserror::Error;
+
+/// Results fetched per request by the paginated iterators.
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+
+#[derive(Debug, Error)]
+pub enum IndexerError {
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Missing indexer configuration in trampoline-env.toml")]
+    MissingEnv,
+}
+
+pub type IndexerResult<T> = std::result::Result<T, IndexerError>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptType {
+    Lock,
+    Type,
+}
+
+/// How `SearchKey::script` is matched. Newer indexers only.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptSearchMode {
+    // Script args start with the given args, the indexer's default
+    Prefix,
+    Exact,
+}
+
+/// How `SearchKeyFilter::output_data` is matched. Newer indexers only.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataFilterMode {
+    Prefix,
+    Exact,
+    Partial,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// Additional conditions on the cells matched by a `SearchKey`.
+/// Ranges are `[inclusive, exclusive)`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SearchKeyFilter {
+    // The other script of the cell: the type script when searching by lock and vice versa
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<Script>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script_len_range: Option<[Uint64; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_data: Option<JsonBytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_data_filter_mode: Option<DataFilterMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_data_len_range: Option<[Uint64; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_capacity_range: Option<[Uint64; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_range: Option<[BlockNumber; 2]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchKey {
+    pub script: Script,
+    pub script_type: ScriptType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script_search_mode: Option<ScriptSearchMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<SearchKeyFilter>,
+}
+
+impl SearchKey {
+    pub fn new(script: Script, script_type: ScriptType) -> Self {
+        SearchKey {
+            script,
+            script_type,
+            script_search_mode: None,
+            filter: None,
+        }
+    }
+
+    pub fn lock(script: Script) -> Self {
+        Self::new(script, ScriptType::Lock)
+    }
+
+    pub fn type_script(script: Script) -> Self {
+        Self::new(script, ScriptType::Type)
+    }
+
+    pub fn search_mode(mut self, mode: ScriptSearchMode) -> Self {
+        self.script_search_mode = Some(mode);
+        self
+    }
+
+    pub fn filter(mut self, filter: SearchKeyFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tip {
+    pub block_hash: H256,
+    pub block_number: BlockNumber,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cell {
+    pub output: CellOutput,
+    pub output_data: JsonBytes,
+    pub out_point: OutPoint,
+    pub block_number: BlockNumber,
+    pub tx_index: Uint32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IoType {
+    Input,
+    Output,
+}
+
+/// A transaction touching a matched cell, once per input or output.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tx {
+    pub tx_hash: H256,
+    pub block_number: BlockNumber,
+    pub tx_index: Uint32,
+    pub io_index: Uint32,
+    pub io_type: IoType,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CellsCapacity {
+    pub capacity: Capacity,
+    pub block_hash: H256,
+    pub block_number: BlockNumber,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pagination<T> {
+    pub objects: Vec<T>,
+    pub last_cursor: JsonBytes,
+}
+
+/// JSON-RPC client of a ckb-indexer.
+#[derive(Debug)]
+pub struct IndexerClient {
+    rpc: RpcClient,
+}
+
+impl IndexerClient {
+    pub fn new(url: &str) -> Self {
+        IndexerClient {
+            rpc: RpcClient::new(url),
+        }
+    }
+
+    pub fn from_env(env: &VirtualEnv) -> Self {
+        Self::new(&format!("{}:{}", env.host, env.host_port))
+    }
+
+    /// Client for the indexer described by `[indexer]` in `trampoline-env.toml`.
+    pub fn for_project(project: &TrampolineProject) -> IndexerResult<Self> {
+        let env = project
+            .config
+            .env
+            .as_ref()
+            .ok_or(IndexerError::MissingEnv)?;
+        Ok(Self::from_env(&env.indexer))
+    }
+
+    pub fn url(&self) -> &str {
+        self.rpc.url()
+    }
+
+    /// Last block indexed, `None` before the first one.
+    pub async fn get_tip(&self) -> IndexerResult<Option<Tip>> {
+        Ok(self.rpc.call("get_tip", json!([])).await?)
+    }
+
+    pub async fn get_cells(
+        &self,
+        search_key: &SearchKey,
+        order: Order,
+        limit: u32,
+        after_cursor: Option<JsonBytes>,
+    ) -> IndexerResult<Pagination<Cell>> {
+        Ok(self
+            .rpc
+            .call(
+                "get_cells",
+                json!([search_key, order, Uint32::from(limit), after_cursor]),
+            )
+            .await?)
+    }
+
+    pub async fn get_transactions(
+        &self,
+        search_key: &SearchKey,
+        order: Order,
+        limit: u32,
+        after_cursor: Option<JsonBytes>,
+    ) -> IndexerResult<Pagination<Tx>> {
+        Ok(self
+            .rpc
+            .call(
+                "get_transactions",
+                json!([search_key, order, Uint32::from(limit), after_cursor]),
+            )
+            .await?)
+    }
+
+    pub async fn get_cells_capacity(
+        &self,
+        search_key: &SearchKey,
+    ) -> IndexerResult<Option<CellsCapacity>> {
+        Ok(self
+            .rpc
+            .call("get_cells_capacity", json!([search_key]))
+            .await?)
+    }
+}
+
+/// Blocking facade over [`IndexerClient`] with iterators over every page of a search.
+/// Must not be used from within an async runtime.
+#[derive(Debug)]
+pub struct BlockingIndexerClient {
+    client: IndexerClient,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl BlockingIndexerClient {
+    pub fn new(client: IndexerClient) -> IndexerResult<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(BlockingIndexerClient { client, runtime })
+    }
+
+    pub fn for_project(project: &TrampolineProject) -> IndexerResult<Self> {
+        Self::new(IndexerClient::for_project(project)?)
+    }
+
+    pub fn client(&self) -> &IndexerClient {
+        &self.client
+    }
+
+    pub fn get_tip(&self) -> IndexerResult<Option<Tip>> {
+        self.runtime.block_on(self.client.get_tip())
+    }
+
+    pub fn get_cells(
+        &self,
+        search_key: &SearchKey,
+        order: Order,
+        limit: u32,
+        after_cursor: Option<JsonBytes>,
+    ) -> IndexerResult<Pagination<Cell>> {
+        self.runtime.block_on(
+            self.client
+                .get_cells(search_key, order, limit, after_cursor),
+        )
+    }
+
+    pub fn get_transactions(
+        &self,
+        search_key: &SearchKey,
+        order: Order,
+        limit: u32,
+        after_cursor: Option<JsonBytes>,
+    ) -> IndexerResult<Pagination<Tx>> {
+        self.runtime.block_on(
+            self.client
+                .get_transactions(search_key, order, limit, after_cursor),
+        )
+    }
+
+    pub fn get_cells_capacity(
+        &self,
+        search_key: &SearchKey,
+    ) -> IndexerResult<Option<CellsCapacity>> {
+        self.runtime
+            .block_on(self.client.get_cells_capacity(search_key))
+    }
+
+    /// Every cell matching `search_key`, fetched `DEFAULT_PAGE_SIZE` at a time.
+    pub fn cells(&self, search_key: SearchKey, order: Order) -> Pages<'_, Cell> {
+        Pages::new(DEFAULT_PAGE_SIZE, move |limit, cursor| {
+            self.get_cells(&search_key, order, limit, cursor)
+        })
+    }
+
+    /// Every transaction matching `search_key`, fetched `DEFAULT_PAGE_SIZE` at a time.
+    pub fn transactions(&self, search_key: SearchKey, order: Order) -> Pages<'_, Tx> {
+        Pages::new(DEFAULT_PAGE_SIZE, move |limit, cursor| {
+            self.get_transactions(&search_key, order, limit, cursor)
+        })
+    }
+}
+
+type FetchPage<'a, T> = Box<dyn FnMut(u32, Option<JsonBytes>) -> IndexerResult<Pagination<T>> + 'a>;
+
+/// Iterator following the indexer's cursors until a page comes back short.
+/// Stops after yielding the first error.
+pub struct Pages<'a, T> {
+    fetch: FetchPage<'a, T>,
+    page_size: u32,
+    buffer: VecDeque<T>,
+    cursor: Option<JsonBytes>,
+    done: bool,
+}
+
+impl<'a, T> Pages<'a, T> {
+    fn new(
+        page_size: u32,
+        fetch: impl FnMut(u32, Option<JsonBytes>) -> IndexerResult<Pagination<T>> + 'a,
+    ) -> Self {
+        Pages {
+            fetch: Box::new(fetch),
+            page_size,
+            buffer: VecDeque::new(),
+            cursor: None,
+            done: false,
+        }
+    }
+
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+}
+
+impl<T> Iterator for Pages<'_, T> {
+    type Item = IndexerResult<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() && !self.done {
+            match (self.fetch)(self.page_size, self.cursor.take()) {
+                Ok(page) => {
+                    self.done = page.objects.len() < self.page_size as usize;
+                    self.cursor = Some(page.last_cursor);
+                    self.buffer.extend(page.objects);
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.buffer.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock;
+    use serde_json::Value;
+
+    fn cells_page(count: usize, cursor: &str) -> String {
+        let cell = json!({
+            "output": {
+                "capacity": "0x174876e800",
+                "lock": {
+                    "code_hash": "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8",
+                    "hash_type": "type",
+                    "args": "0x470dcdc5e44064909650113a274b3b36aecb6dc7",
+                },
+                "type": null,
+            },
+            "output_data": "0x",
+            "out_point": {
+                "tx_hash": "0xe2fb199810d49a4d8beec56718ba2593b665db9d52299a0f9e6e75416d73ff5c",
+                "index": "0x0",
+            },
+            "block_number": "0x1",
+            "tx_index": "0x0",
+        });
+        json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "result": { "objects": vec![cell; count], "last_cursor": cursor },
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_cells_follow_cursor() {
+        let (url, server) = mock::serve(vec![cells_page(2, "0x01"), cells_page(1, "0x02")]);
+        let indexer = BlockingIndexerClient::new(IndexerClient::new(&url)).unwrap();
+        let key = SearchKey::lock(Script::default()).search_mode(ScriptSearchMode::Exact);
+        let cells = indexer
+            .cells(key, Order::Asc)
+            .page_size(2)
+            .collect::<IndexerResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(cells.len(), 3);
+
+        let requests = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|request| serde_json::from_str::<Value>(request).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(requests[0]["params"][0]["script_search_mode"], "exact");
+        assert_eq!(requests[0]["params"][2], "0x2");
+        assert_eq!(requests[0]["params"][3], Value::Null);
+        assert_eq!(requests[1]["params"][3], "0x01");
+    }
+}
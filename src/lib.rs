@@ -1,7 +1,17 @@
+pub mod account;
+pub mod address;
 pub mod docker;
+pub mod faucet;
+pub mod indexer;
+pub mod network;
 pub mod opts;
+pub mod pattern;
 pub mod project;
+pub mod rpc;
 pub mod schema;
+pub mod script;
+pub mod testing;
+pub mod transaction;
 mod utils;
 
 use anyhow::{anyhow, Result};
@@ -0,0 +1,903 @@
+mod native;
+mod spec;
+
+pub use native::NativeNetwork;
+pub use spec::{dev_spec_path, render_dev_spec, with_project_accounts};
+
+use crate::account::AccountError;
+use crate::docker::{
+    backend_from_config, DockerBackend, DockerContainer, DockerError, DockerImage, DockerPort,
+    LogOptions, Volume,
+};
+use crate::project::{RuntimeConfig, TrampolineProject, TrampolineProjectError, VirtualEnv};
+use crate::rpc::{BlockingRpcClient, RpcClient, RpcError};
+
+use ckb_jsonrpc_types::HeaderView;
+use ckb_types::core::EpochNumberWithFraction;
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum NetworkError {
+    #[error(transparent)]
+    Docker(#[from] DockerError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Project(#[from] TrampolineProjectError),
+    #[error(transparent)]
+    Account(#[from] AccountError),
+    #[error(transparent)]
+    DeserializeToml(#[from] toml::de::Error),
+    #[error(transparent)]
+    SerializeToml(#[from] toml::ser::Error),
+    #[error("Invalid chain spec: {0}")]
+    Spec(String),
+    #[error(
+        "Chain data in {0} was created from a different spec. Rerun with `--reset` to delete it"
+    )]
+    ChainDataExists(String),
+    #[error("No {0} found. Has the network been launched?")]
+    NotLaunched(NetworkService),
+    #[error("{0} is already running")]
+    AlreadyRunning(NetworkService),
+    #[error("Could not run `{0}`. Is it installed and on your PATH?")]
+    MissingBinary(String),
+    #[error(
+        "{service} did not become ready within {waited_secs}s: {reason}\nRecent logs:\n{logs}"
+    )]
+    NotReady {
+        service: NetworkService,
+        waited_secs: u64,
+        reason: String,
+        logs: String,
+    },
+    #[error("Missing network configuration in trampoline-env.toml")]
+    MissingEnv,
+    #[error("A miner is already running. Stop it with `trampoline network miner --stop` first")]
+    MinerRunning,
+}
+
+pub type NetworkResult<T> = std::result::Result<T, NetworkError>;
+
+// Command line prefix of the miner process started inside the node container
+const MINER_PROCESS: &str = "ckb miner";
+// `ckb miner` logs to a file under its data directory instead of the container output
+const MINER_LOG_FILE: &str = "data/logs/miner.log";
+// Lines of output included in readiness errors
+const READINESS_LOG_LINES: usize = 30;
+const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Every container belonging to a project joins this network so services can
+// reach each other by container name rather than through the host.
+pub fn network_name(project: &TrampolineProject) -> String {
+    format!("{}-network", project.config.name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NetworkService {
+    Chain,
+    Miner,
+    Indexer,
+}
+
+impl NetworkService {
+    pub fn all() -> [NetworkService; 3] {
+        [Self::Chain, Self::Miner, Self::Indexer]
+    }
+
+    // The miner runs inside the node container, so both share a name.
+    pub fn container_name(&self, project: &TrampolineProject) -> String {
+        match self {
+            Self::Chain | Self::Miner => format!("{}-node", project.config.name),
+            Self::Indexer => format!("{}-indexer", project.config.name),
+        }
+    }
+
+    pub fn virtual_env<'a>(&self, project: &'a TrampolineProject) -> Option<&'a VirtualEnv> {
+        let env = project.config.env.as_ref()?;
+        match self {
+            Self::Chain => Some(&env.chain),
+            Self::Miner => Some(&env.miner),
+            Self::Indexer => Some(&env.indexer),
+        }
+    }
+
+    // URL other containers on the project network use to reach this service
+    pub fn internal_rpc_url(&self, project: &TrampolineProject) -> Option<String> {
+        let env = self.virtual_env(project)?;
+        Some(format!(
+            "http://{}:{}",
+            self.container_name(project),
+            env.container_port
+        ))
+    }
+
+    // URL the host uses to reach this service
+    pub fn rpc_url(&self, project: &TrampolineProject) -> Option<String> {
+        let env = self.virtual_env(project)?;
+        Some(format!("{}:{}", env.host, env.host_port))
+    }
+}
+
+impl std::fmt::Display for NetworkService {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Chain => write!(f, "chain"),
+            Self::Miner => write!(f, "miner"),
+            Self::Indexer => write!(f, "indexer"),
+        }
+    }
+}
+
+impl FromStr for NetworkService {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chain" | "node" => Ok(Self::Chain),
+            "miner" => Ok(Self::Miner),
+            "indexer" | "index" => Ok(Self::Indexer),
+            _ => Err(format!(
+                "Unknown service {}. Expected one of: chain, miner, indexer",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceStatus {
+    pub service: NetworkService,
+    // Container name, or process id for the native backend
+    pub instance: String,
+    pub running: bool,
+    // False when the container or process was never started
+    pub created: bool,
+    pub state: String,
+    pub ports: Vec<String>,
+    pub data: Vec<String>,
+}
+
+impl std::fmt::Display for ServiceStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = if self.running { "running" } else { "stopped" };
+        writeln!(f, "[{}] {}", self.service, state)?;
+        writeln!(f, "Instance: {} ({})", self.instance, self.state)?;
+        if !self.ports.is_empty() {
+            writeln!(f, "Ports: {}", self.ports.join(", "))?;
+        }
+        for data in &self.data {
+            writeln!(f, "Data: {}", data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lifecycle of the chain, miner and indexer of a project, independent of
+/// whether they run in containers or as local processes.
+pub trait NetworkBackend {
+    fn project(&self) -> &TrampolineProject;
+    /// Generates `ckb.toml` and the bundled dev spec in the chain directory
+    /// with `ckb init --chain dev`.
+    fn init_node(&self) -> NetworkResult<()>;
+    /// Starts the node. With `attach`, runs in the foreground until it exits.
+    fn launch(&self, attach: bool) -> NetworkResult<()>;
+    fn launch_indexer(&self) -> NetworkResult<()>;
+    /// Starts mining continuously in the background.
+    fn start_miner(&self) -> NetworkResult<()>;
+    /// Mines exactly `blocks` blocks in the foreground and returns once they are sealed.
+    fn mine_blocks(&self, blocks: u64) -> NetworkResult<()>;
+    fn restart(&self, service: NetworkService) -> NetworkResult<()>;
+    fn service_status(&self, service: NetworkService) -> NetworkResult<ServiceStatus>;
+    /// Stops a single service, or every service when none is given.
+    fn stop(&self, service: Option<NetworkService>) -> NetworkResult<()>;
+    /// Stops every service and removes what `launch` created, keeping chain data.
+    fn down(&self) -> NetworkResult<()>;
+    fn logs(&self, service: NetworkService, options: &LogOptions) -> NetworkResult<()>;
+    /// Returns the last `tail` lines of output, used to explain failures.
+    fn recent_logs(&self, service: NetworkService, tail: usize) -> NetworkResult<String>;
+
+    fn status(&self) -> NetworkResult<Vec<ServiceStatus>> {
+        NetworkService::all()
+            .iter()
+            .map(|service| self.service_status(*service))
+            .collect()
+    }
+
+    // Writes the dev spec rendered from `[chain.spec]`, running `ckb init` first
+    // on a fresh project. A different genesis invalidates existing chain data, so
+    // that data is only deleted when `reset` is given.
+    fn init(&self, reset: bool) -> NetworkResult<()> {
+        let project = self.project();
+        let chain_env = NetworkService::Chain
+            .virtual_env(project)
+            .ok_or(NetworkError::MissingEnv)?;
+        let chain_dir = project.root_dir.join(&chain_env.local_binding);
+        let spec_path = dev_spec_path(&chain_dir);
+        let spec_config =
+            with_project_accounts(&chain_env.spec.clone().unwrap_or_default(), project)?;
+        let spec = render_dev_spec(&spec_config)?;
+
+        let initialized = chain_dir.join("ckb.toml").exists();
+        let unchanged = std::fs::read_to_string(&spec_path).ok().as_deref() == Some(spec.as_str());
+        if initialized && unchanged && !reset {
+            println!("Network already initialized in {}", chain_dir.display());
+            return Ok(());
+        }
+        if self.service_status(NetworkService::Chain)?.running {
+            return Err(NetworkError::AlreadyRunning(NetworkService::Chain));
+        }
+
+        let chain_data = chain_dir.join("data");
+        if chain_data.exists() {
+            if !reset {
+                return Err(NetworkError::ChainDataExists(
+                    chain_data.display().to_string(),
+                ));
+            }
+            std::fs::remove_dir_all(&chain_data)?;
+            if let Some(indexer_env) = NetworkService::Indexer.virtual_env(project) {
+                let indexer_dir = project.root_dir.join(&indexer_env.local_binding);
+                if indexer_dir.exists() {
+                    std::fs::remove_dir_all(&indexer_dir)?;
+                }
+                std::fs::create_dir_all(&indexer_dir)?;
+            }
+            println!("Deleted chain and indexer data");
+        }
+
+        if !initialized {
+            std::fs::create_dir_all(&chain_dir)?;
+            self.init_node()?;
+        }
+        std::fs::write(&spec_path, spec)?;
+        println!("Wrote chain spec to {}", spec_path.display());
+        Ok(())
+    }
+
+    // Polls the service's RPC until it answers. Gives up early if the service
+    // exits, and includes its latest output in the error either way.
+    fn wait_until_ready(&self, service: NetworkService, timeout: Duration) -> NetworkResult<()> {
+        let started = Instant::now();
+        let reason = loop {
+            let error = match probe(self.project(), service) {
+                Ok(()) => {
+                    println!("{} is ready", service);
+                    return Ok(());
+                }
+                Err(e) => e,
+            };
+            if !self.service_status(service)?.running {
+                break format!("{} exited", service);
+            }
+            if started.elapsed() >= timeout {
+                break error.to_string();
+            }
+            thread::sleep(READINESS_POLL_INTERVAL);
+        };
+        Err(NetworkError::NotReady {
+            service,
+            waited_secs: started.elapsed().as_secs(),
+            reason,
+            logs: self
+                .recent_logs(service, READINESS_LOG_LINES)
+                .unwrap_or_default(),
+        })
+    }
+
+    // Mines until the tip reaches the first block of `target_epoch`. Epoch lengths
+    // can change between epochs, so the remaining distance is recomputed after each batch.
+    fn mine_until_epoch(&self, target_epoch: u64) -> NetworkResult<()> {
+        loop {
+            let tip = tip_header(self.project())?;
+            let epoch = EpochNumberWithFraction::from_full_value(tip.inner.epoch.value());
+            if epoch.number() >= target_epoch {
+                println!(
+                    "Reached epoch {} at block {}",
+                    epoch.number(),
+                    tip.inner.number.value()
+                );
+                return Ok(());
+            }
+            let remaining = (target_epoch - epoch.number() - 1) * epoch.length()
+                + (epoch.length() - epoch.index());
+            self.mine_blocks(remaining.max(1))?;
+        }
+    }
+}
+
+pub fn runtime_config(project: &TrampolineProject) -> RuntimeConfig {
+    project
+        .config
+        .env
+        .as_ref()
+        .map(|env| env.runtime.clone())
+        .unwrap_or_default()
+}
+
+/// Picks the backend selected by `engine` under `[runtime]`. In `auto` mode,
+/// falls back to local `ckb` binaries when no container runtime is installed.
+pub fn backend_for(project: &TrampolineProject) -> NetworkResult<Box<dyn NetworkBackend + '_>> {
+    let runtime = runtime_config(project);
+    if runtime.engine.as_deref() == Some("native") {
+        return Ok(Box::new(NativeNetwork::new(project)));
+    }
+    match backend_from_config(Some(&runtime)) {
+        Ok(backend) => Ok(Box::new(ContainerNetwork::new(project, backend))),
+        Err(DockerError::NoRuntime) if NativeNetwork::new(project).is_available() => {
+            println!("No container runtime found, running ckb natively");
+            Ok(Box::new(NativeNetwork::new(project)))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Runs the services of a project's local network in containers through a [`DockerBackend`].
+pub struct ContainerNetwork<'a> {
+    project: &'a TrampolineProject,
+    backend: Box<dyn DockerBackend>,
+}
+
+impl<'a> ContainerNetwork<'a> {
+    pub fn new(project: &'a TrampolineProject, backend: Box<dyn DockerBackend>) -> Self {
+        Self { project, backend }
+    }
+
+    pub fn backend(&self) -> &dyn DockerBackend {
+        self.backend.as_ref()
+    }
+
+    fn runtime(&self) -> RuntimeConfig {
+        runtime_config(self.project)
+    }
+
+    fn env(&self, service: NetworkService) -> NetworkResult<&'a VirtualEnv> {
+        service
+            .virtual_env(self.project)
+            .ok_or(NetworkError::MissingEnv)
+    }
+
+    // Bind mounts must exist on the host before a container can use them
+    fn volume_path(&self, env: &VirtualEnv) -> NetworkResult<std::path::PathBuf> {
+        let path = self.project.root_dir.join(&env.local_binding);
+        std::fs::create_dir_all(&path).map_err(DockerError::from)?;
+        Ok(path)
+    }
+
+    // Kills processes inside a container whose command line starts with `cmd_prefix`.
+    // Scans /proc directly since the ckb image does not ship procps.
+    fn kill_processes(&self, container_name: &str, cmd_prefix: &str) -> NetworkResult<()> {
+        let script = format!(
+            "for p in /proc/[0-9]*; do pid=${{p#/proc/}}; [ \"$pid\" = \"$$\" ] && continue; \
+             tr '\\0' ' ' < $p/cmdline 2>/dev/null | grep -q '^{}' && kill $pid; done; true",
+            cmd_prefix
+        );
+        self.backend
+            .exec(container_name, &["bash", "-c", script.as_str()], false)?;
+        Ok(())
+    }
+
+    // `stop` keeps containers, so a stopped one is started again instead of recreated.
+    // An attached launch needs a new container to stream its output.
+    fn run_service(
+        &self,
+        service: NetworkService,
+        container: &DockerContainer,
+        args: &[String],
+        detach: bool,
+    ) -> NetworkResult<()> {
+        match self.backend.inspect(&container.name)? {
+            Some(status) if status.running => return Err(NetworkError::AlreadyRunning(service)),
+            Some(_) if detach => self.backend.start(&container.name)?,
+            Some(_) => {
+                self.backend.remove(&container.name)?;
+                self.backend.run(container, args, detach)?;
+            }
+            None => {
+                self.backend.run(container, args, detach)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl NetworkBackend for ContainerNetwork<'_> {
+    fn project(&self) -> &TrampolineProject {
+        self.project
+    }
+
+    // Runs `ckb init` in a throwaway container sharing the node's volume. The
+    // generated config binds the RPC to 127.0.0.1, which the published port cannot
+    // reach, so it is rebound to all interfaces of the container.
+    fn init_node(&self) -> NetworkResult<()> {
+        let runtime = self.runtime();
+        self.backend
+            .build_image(&self.project.root_dir, runtime.image())?;
+
+        let env = self.env(NetworkService::Chain)?;
+        let host_volume = self.volume_path(env)?;
+        let container = DockerContainer {
+            name: format!("{}-init", self.project.config.name),
+            port_bindings: vec![],
+            volumes: vec![Volume {
+                host: &host_volume,
+                container: Path::new(&env.container_mount),
+            }],
+            env_vars: HashMap::default(),
+            image: DockerImage::from_reference(runtime.image()),
+            network: None,
+        };
+        let args = vec![
+            "init".to_string(),
+            "--chain".to_string(),
+            "dev".to_string(),
+            "--rpc-port".to_string(),
+            env.container_port.to_string(),
+        ];
+        if self.backend.inspect(&container.name)?.is_some() {
+            self.backend.remove(&container.name)?;
+        }
+        let result = self.backend.run(&container, &args, false);
+        self.backend.remove(&container.name)?;
+        result?;
+        self.project
+            .set_rpc_listen_address(&format!("0.0.0.0:{}", env.container_port))?;
+        Ok(())
+    }
+
+    // Builds the node image, then starts the node on the project network.
+    // With `attach`, the node runs in the foreground until it exits.
+    fn launch(&self, attach: bool) -> NetworkResult<()> {
+        let runtime = self.runtime();
+        self.backend
+            .build_image(&self.project.root_dir, runtime.image())?;
+
+        let env = self.env(NetworkService::Chain)?;
+        let host_volume = self.volume_path(env)?;
+        let network = network_name(self.project);
+        self.backend.create_network(&network)?;
+
+        let container = DockerContainer {
+            name: NetworkService::Chain.container_name(self.project),
+            port_bindings: vec![DockerPort {
+                host: env.host_port,
+                container: env.container_port,
+            }],
+            volumes: vec![Volume {
+                host: &host_volume,
+                container: Path::new(&env.container_mount),
+            }],
+            env_vars: HashMap::default(),
+            image: DockerImage::from_reference(runtime.image()),
+            network: Some(network),
+        };
+        if !attach {
+            println!("Starting node in container: {}", container.name);
+            println!("{}", env);
+        }
+        self.run_service(
+            NetworkService::Chain,
+            &container,
+            &["run".to_string()],
+            !attach,
+        )
+    }
+
+    // Starts ckb-indexer on the project network, pointed at the node's RPC
+    fn launch_indexer(&self) -> NetworkResult<()> {
+        let chain_url = NetworkService::Chain
+            .internal_rpc_url(self.project)
+            .ok_or(NetworkError::MissingEnv)?;
+        let env = self.env(NetworkService::Indexer)?;
+        let host_volume = self.volume_path(env)?;
+        let network = network_name(self.project);
+        self.backend.create_network(&network)?;
+
+        let container = DockerContainer {
+            name: NetworkService::Indexer.container_name(self.project),
+            port_bindings: vec![DockerPort {
+                host: env.host_port,
+                container: env.container_port,
+            }],
+            volumes: vec![Volume {
+                host: &host_volume,
+                container: Path::new(&env.container_mount),
+            }],
+            env_vars: HashMap::default(),
+            image: DockerImage::from_reference(self.runtime().indexer_image()),
+            network: Some(network),
+        };
+        let args = vec![
+            "ckb-indexer".to_string(),
+            "-c".to_string(),
+            chain_url,
+            "-l".to_string(),
+            format!("0.0.0.0:{}", env.container_port),
+            "-s".to_string(),
+            env.container_mount.clone(),
+        ];
+        self.run_service(NetworkService::Indexer, &container, &args, true)?;
+
+        println!("Started indexer in container: {}", container.name);
+        println!(
+            "Indexer RPC: {}",
+            NetworkService::Indexer.rpc_url(self.project).unwrap()
+        );
+        println!("{}", env);
+        Ok(())
+    }
+
+    // Starts `ckb miner` in the background inside the node container
+    fn start_miner(&self) -> NetworkResult<()> {
+        let status = self.service_status(NetworkService::Miner)?;
+        if !status.created {
+            return Err(NetworkError::NotLaunched(NetworkService::Chain));
+        }
+        if status.running {
+            return Err(NetworkError::MinerRunning);
+        }
+        let mount = &self.env(NetworkService::Miner)?.container_mount;
+        self.backend.exec(
+            &status.instance,
+            &["ckb", "-C", mount.as_str(), "miner"],
+            true,
+        )?;
+        println!("Started miner in container: {}", status.instance);
+        Ok(())
+    }
+
+    fn restart(&self, service: NetworkService) -> NetworkResult<()> {
+        Ok(self
+            .backend
+            .restart(&service.container_name(self.project))?)
+    }
+
+    fn service_status(&self, service: NetworkService) -> NetworkResult<ServiceStatus> {
+        let container_name = service.container_name(self.project);
+        let container = self.backend.inspect(&container_name)?;
+        let container_running = container.as_ref().map(|c| c.running).unwrap_or(false);
+        let running = match service {
+            NetworkService::Miner if container_running => self
+                .backend
+                .processes(&container_name)?
+                .iter()
+                .any(|process| process.starts_with(MINER_PROCESS)),
+            _ => container_running,
+        };
+        let state = match (&container, service) {
+            (None, _) => "not created".to_string(),
+            (Some(_), NetworkService::Miner) if running => "running".to_string(),
+            (Some(_), NetworkService::Miner) => "stopped".to_string(),
+            (Some(container), _) => container.state.clone(),
+        };
+        Ok(ServiceStatus {
+            service,
+            instance: container_name,
+            running,
+            created: container.is_some(),
+            state,
+            ports: container
+                .as_ref()
+                .map(|c| c.ports.clone())
+                .unwrap_or_default(),
+            data: container.map(|c| c.mounts).unwrap_or_default(),
+        })
+    }
+
+    // Stops a single service, or every service when none is given. Stopped
+    // containers are kept so they can be inspected; use `down` to remove them.
+    fn stop(&self, service: Option<NetworkService>) -> NetworkResult<()> {
+        let services = match service {
+            Some(service) => vec![service],
+            None => vec![
+                NetworkService::Indexer,
+                NetworkService::Miner,
+                NetworkService::Chain,
+            ],
+        };
+        for service in services {
+            let status = self.service_status(service)?;
+            if !status.running {
+                continue;
+            }
+            if service == NetworkService::Miner {
+                self.kill_processes(&status.instance, MINER_PROCESS)?;
+            } else {
+                self.backend.stop(&status.instance)?;
+            }
+            println!("Stopped {} ({})", service, status.instance);
+        }
+        Ok(())
+    }
+
+    // Stops and removes every container of the project along with its network.
+    // Chain and indexer data under `.trampoline/network` is left untouched.
+    fn down(&self) -> NetworkResult<()> {
+        self.stop(None)?;
+        for service in [NetworkService::Indexer, NetworkService::Chain] {
+            let container_name = service.container_name(self.project);
+            if self.backend.inspect(&container_name)?.is_none() {
+                continue;
+            }
+            self.backend.remove(&container_name)?;
+            println!("Removed container {}", container_name);
+        }
+        self.backend.remove_network(&network_name(self.project))?;
+        Ok(())
+    }
+
+    // Prints the output of a service. The chain and indexer log to their container
+    // output; the miner is exec'd into the node container so its log file is tailed.
+    fn logs(&self, service: NetworkService, options: &LogOptions) -> NetworkResult<()> {
+        let container_name = service.container_name(self.project);
+        if self.backend.inspect(&container_name)?.is_none() {
+            return Err(NetworkError::NotLaunched(service));
+        }
+        match service {
+            NetworkService::Miner => {
+                if options.since.is_some() {
+                    eprintln!("Note: --since is not supported for miner logs and will be ignored");
+                }
+                let mount = &self.env(service)?.container_mount;
+                let log_path = format!("{}/{}", mount, MINER_LOG_FILE);
+                let tail = options.tail.unwrap_or(10).to_string();
+                let mut args = vec!["tail", "-n", tail.as_str()];
+                if options.follow {
+                    args.push("-F");
+                }
+                args.push(log_path.as_str());
+                Ok(self.backend.exec(&container_name, &args, false)?)
+            }
+            _ => Ok(self.backend.logs(&container_name, options)?),
+        }
+    }
+
+    fn recent_logs(&self, service: NetworkService, tail: usize) -> NetworkResult<String> {
+        Ok(self
+            .backend
+            .recent_logs(&service.container_name(self.project), tail)?)
+    }
+
+    fn mine_blocks(&self, blocks: u64) -> NetworkResult<()> {
+        let status = self.service_status(NetworkService::Miner)?;
+        if !status.created {
+            return Err(NetworkError::NotLaunched(NetworkService::Chain));
+        }
+        if status.running {
+            return Err(NetworkError::MinerRunning);
+        }
+        let mount = &self.env(NetworkService::Miner)?.container_mount;
+        let limit = blocks.to_string();
+        self.backend.exec(
+            &status.instance,
+            &[
+                "ckb",
+                "-C",
+                mount.as_str(),
+                "miner",
+                "--limit",
+                limit.as_str(),
+            ],
+            false,
+        )?;
+        Ok(())
+    }
+}
+
+// Short timeout so readiness polling notices a hung service
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn service_client(
+    project: &TrampolineProject,
+    service: NetworkService,
+) -> NetworkResult<BlockingRpcClient> {
+    let url = service.rpc_url(project).ok_or(NetworkError::MissingEnv)?;
+    Ok(BlockingRpcClient::new(RpcClient::with_timeout(
+        &url,
+        PROBE_TIMEOUT,
+    ))?)
+}
+
+// Fetches the tip header through the chain's JSON-RPC endpoint on the host
+pub fn tip_header(project: &TrampolineProject) -> NetworkResult<HeaderView> {
+    Ok(service_client(project, NetworkService::Chain)?.get_tip_header()?)
+}
+
+// A service is ready once it answers its cheapest RPC call. The miner has no
+// RPC, so it counts as ready as soon as it runs.
+pub fn probe(project: &TrampolineProject, service: NetworkService) -> NetworkResult<()> {
+    match service {
+        NetworkService::Chain => tip_header(project).map(|_| ()),
+        NetworkService::Indexer => {
+            service_client(project, service)?
+                .call::<serde_json::Value>("get_tip", serde_json::json!([]))?;
+            Ok(())
+        }
+        NetworkService::Miner => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::{ContainerStatus, DockerResult};
+    use crate::project::{TrampolineConfig, TrampolineEnv};
+    use std::sync::{Arc, Mutex};
+
+    // Keeps containers in memory, as name -> running, and records the calls made
+    #[derive(Clone, Default)]
+    struct FakeBackend {
+        containers: Arc<Mutex<HashMap<String, bool>>>,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl FakeBackend {
+        fn record(&self, call: &str, name: &str) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", call, name));
+        }
+
+        fn set_running(&self, name: &str, running: bool) -> DockerResult<()> {
+            match self.containers.lock().unwrap().get_mut(name) {
+                Some(state) => {
+                    *state = running;
+                    Ok(())
+                }
+                None => Err(DockerError::CommandFailed {
+                    command: name.to_string(),
+                    stderr: "No such container".to_string(),
+                }),
+            }
+        }
+    }
+
+    impl DockerBackend for FakeBackend {
+        fn create_network(&self, _name: &str) -> DockerResult<()> {
+            Ok(())
+        }
+
+        fn remove_network(&self, _name: &str) -> DockerResult<()> {
+            Ok(())
+        }
+
+        fn run(
+            &self,
+            container: &DockerContainer,
+            _args: &[String],
+            detach: bool,
+        ) -> DockerResult<String> {
+            self.record("run", &container.name);
+            let mut containers = self.containers.lock().unwrap();
+            if containers.contains_key(&container.name) {
+                return Err(DockerError::CommandFailed {
+                    command: format!("run --name {}", container.name),
+                    stderr: "Conflict. The container name is already in use".to_string(),
+                });
+            }
+            // Attached containers have exited by the time `run` returns
+            containers.insert(container.name.clone(), detach);
+            Ok(container.name.clone())
+        }
+
+        fn start(&self, name: &str) -> DockerResult<()> {
+            self.record("start", name);
+            self.set_running(name, true)
+        }
+
+        fn stop(&self, name: &str) -> DockerResult<()> {
+            self.record("stop", name);
+            self.set_running(name, false)
+        }
+
+        fn restart(&self, name: &str) -> DockerResult<()> {
+            self.start(name)
+        }
+
+        fn remove(&self, name: &str) -> DockerResult<()> {
+            self.record("remove", name);
+            self.containers.lock().unwrap().remove(name);
+            Ok(())
+        }
+
+        fn inspect(&self, name: &str) -> DockerResult<Option<ContainerStatus>> {
+            Ok(self
+                .containers
+                .lock()
+                .unwrap()
+                .get(name)
+                .map(|&running| ContainerStatus {
+                    name: name.to_string(),
+                    state: if running { "running" } else { "exited" }.to_string(),
+                    running,
+                    ports: vec![],
+                    mounts: vec![],
+                }))
+        }
+
+        fn processes(&self, _name: &str) -> DockerResult<Vec<String>> {
+            Ok(vec![])
+        }
+
+        fn exec(&self, _name: &str, _args: &[&str], _detach: bool) -> DockerResult<()> {
+            Ok(())
+        }
+
+        fn logs(&self, _name: &str, _options: &LogOptions) -> DockerResult<()> {
+            Ok(())
+        }
+
+        fn recent_logs(&self, _name: &str, _tail: usize) -> DockerResult<String> {
+            Ok(String::new())
+        }
+
+        fn build_image(&self, _context: &Path, _tag: &str) -> DockerResult<()> {
+            Ok(())
+        }
+    }
+
+    fn project(root_dir: &Path) -> TrampolineProject {
+        let env = toml::from_str::<TrampolineEnv>(include_str!("../templates/trampoline-env.toml"))
+            .unwrap();
+        TrampolineProject {
+            config: TrampolineConfig {
+                name: "demo".to_string(),
+                env: Some(env),
+                ..Default::default()
+            },
+            root_dir: root_dir.to_path_buf(),
+        }
+    }
+
+    #[test]
+    fn test_launch_after_stop_reuses_containers() {
+        let root_dir =
+            std::env::temp_dir().join(format!("trampoline-network-{}", std::process::id()));
+        let project = project(&root_dir);
+        let backend = FakeBackend::default();
+        let network = ContainerNetwork::new(&project, Box::new(backend.clone()));
+
+        network.launch(false).unwrap();
+        network.launch_indexer().unwrap();
+        assert!(matches!(
+            network.launch(false),
+            Err(NetworkError::AlreadyRunning(NetworkService::Chain))
+        ));
+        network.stop(None).unwrap();
+        network.launch(false).unwrap();
+        network.launch_indexer().unwrap();
+        // An attached launch leaves an exited container behind
+        network.stop(Some(NetworkService::Chain)).unwrap();
+        network.launch(true).unwrap();
+        network.launch(false).unwrap();
+        std::fs::remove_dir_all(&root_dir).unwrap();
+
+        let calls = backend.calls.lock().unwrap().clone();
+        assert_eq!(
+            calls,
+            vec![
+                "run demo-node",
+                "run demo-indexer",
+                "stop demo-indexer",
+                "stop demo-node",
+                "start demo-node",
+                "start demo-indexer",
+                "stop demo-node",
+                "remove demo-node",
+                "run demo-node",
+                "start demo-node",
+            ]
+        );
+    }
+}
@@ -0,0 +1,467 @@
+use super::{
+    runtime_config, NetworkBackend, NetworkError, NetworkResult, NetworkService, ServiceStatus,
+};
+use crate::docker::LogOptions;
+use crate::project::{TrampolineProject, VirtualEnv, TRAMPOLINE_FOLDER};
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const CKB_BIN: &str = "ckb";
+pub const CKB_INDEXER_BIN: &str = "ckb-indexer";
+
+const PID_DIR: &str = "pids";
+const LOG_DIR: &str = "logs";
+// How long a service gets to shut down after SIGTERM before it is killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs `ckb`, `ckb miner` and `ckb-indexer` as local child processes, for
+/// machines without a container runtime. Data lives in the same directories
+/// the containers mount, and PIDs are tracked under `.trampoline/pids`.
+pub struct NativeNetwork<'a> {
+    project: &'a TrampolineProject,
+}
+
+impl<'a> NativeNetwork<'a> {
+    pub fn new(project: &'a TrampolineProject) -> Self {
+        Self { project }
+    }
+
+    fn ckb_bin(&self) -> String {
+        runtime_config(self.project)
+            .ckb_bin
+            .unwrap_or_else(|| CKB_BIN.to_string())
+    }
+
+    fn indexer_bin(&self) -> String {
+        runtime_config(self.project)
+            .indexer_bin
+            .unwrap_or_else(|| CKB_INDEXER_BIN.to_string())
+    }
+
+    pub fn is_available(&self) -> bool {
+        Command::new(self.ckb_bin())
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+
+    fn env(&self, service: NetworkService) -> NetworkResult<&'a VirtualEnv> {
+        service
+            .virtual_env(self.project)
+            .ok_or(NetworkError::MissingEnv)
+    }
+
+    fn data_dir(&self, service: NetworkService) -> NetworkResult<PathBuf> {
+        let path = self
+            .project
+            .root_dir
+            .join(&self.env(service)?.local_binding);
+        fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+
+    fn trampoline_dir(&self, dir: &str) -> NetworkResult<PathBuf> {
+        let path = self.project.root_dir.join(TRAMPOLINE_FOLDER).join(dir);
+        fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+
+    fn pid_file(&self, service: NetworkService) -> NetworkResult<PathBuf> {
+        Ok(self
+            .trampoline_dir(PID_DIR)?
+            .join(format!("{}.pid", service)))
+    }
+
+    fn log_file(&self, service: NetworkService) -> NetworkResult<PathBuf> {
+        Ok(self
+            .trampoline_dir(LOG_DIR)?
+            .join(format!("{}.log", service)))
+    }
+
+    fn pid(&self, service: NetworkService) -> NetworkResult<Option<u32>> {
+        let pid_file = self.pid_file(service)?;
+        if !pid_file.exists() {
+            return Ok(None);
+        }
+        Ok(fs::read_to_string(pid_file)?.trim().parse::<u32>().ok())
+    }
+
+    fn program(&self, service: NetworkService) -> String {
+        match service {
+            NetworkService::Indexer => self.indexer_bin(),
+            NetworkService::Chain | NetworkService::Miner => self.ckb_bin(),
+        }
+    }
+
+    // Deletes the PID file when its process is gone or the PID now belongs to
+    // another program, so a reused PID is never signalled
+    fn running_pid(&self, service: NetworkService) -> NetworkResult<Option<u32>> {
+        let pid = match self.pid(service)? {
+            Some(pid) => pid,
+            None => return Ok(None),
+        };
+        if is_process_of(pid, &self.program(service)) {
+            return Ok(Some(pid));
+        }
+        fs::remove_file(self.pid_file(service)?)?;
+        Ok(None)
+    }
+
+    // Same layout the container entrypoint creates on first start, with the
+    // RPC listening on the port the host would otherwise have mapped
+    fn ensure_initialized(&self) -> NetworkResult<PathBuf> {
+        let chain_dir = self.data_dir(NetworkService::Chain)?;
+        if chain_dir.join("ckb.toml").exists() {
+            return Ok(chain_dir);
+        }
+        let rpc_port = self.env(NetworkService::Chain)?.host_port.to_string();
+        let status = Command::new(self.ckb_bin())
+            .arg("-C")
+            .arg(&chain_dir)
+            .args(["init", "--chain", "dev", "--rpc-port", rpc_port.as_str()])
+            .stdin(Stdio::null())
+            .status()
+            .map_err(|_| NetworkError::MissingBinary(self.ckb_bin()))?;
+        if !status.success() {
+            return Err(NetworkError::Io(std::io::Error::other(format!(
+                "ckb init exited with {}",
+                status
+            ))));
+        }
+        Ok(chain_dir)
+    }
+
+    // Starts a service in the background, sending its output to `.trampoline/logs`
+    fn spawn(&self, service: NetworkService, program: &str, args: &[String]) -> NetworkResult<u32> {
+        let log_path = self.log_file(service)?;
+        let log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)?;
+        let child = Command::new(program)
+            .args(args)
+            .current_dir(&self.project.root_dir)
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()
+            .map_err(|_| NetworkError::MissingBinary(program.to_string()))?;
+        fs::write(self.pid_file(service)?, child.id().to_string())?;
+        println!(
+            "Started {} (pid {}), logging to {}",
+            service,
+            child.id(),
+            log_path.display()
+        );
+        Ok(child.id())
+    }
+
+    fn chain_args(&self, chain_dir: &Path, command: &[&str]) -> Vec<String> {
+        let mut args = vec!["-C".to_string(), chain_dir.display().to_string()];
+        args.extend(command.iter().map(|arg| arg.to_string()));
+        args
+    }
+
+    fn start(&self, service: NetworkService) -> NetworkResult<()> {
+        match service {
+            NetworkService::Chain => self.launch(false),
+            NetworkService::Miner => self.start_miner(),
+            NetworkService::Indexer => self.launch_indexer(),
+        }
+    }
+}
+
+impl NetworkBackend for NativeNetwork<'_> {
+    fn project(&self) -> &TrampolineProject {
+        self.project
+    }
+
+    fn init_node(&self) -> NetworkResult<()> {
+        self.ensure_initialized().map(|_| ())
+    }
+
+    fn launch(&self, attach: bool) -> NetworkResult<()> {
+        if self.running_pid(NetworkService::Chain)?.is_some() {
+            return Err(NetworkError::AlreadyRunning(NetworkService::Chain));
+        }
+        let chain_dir = self.ensure_initialized()?;
+        let args = self.chain_args(&chain_dir, &["run"]);
+        if !attach {
+            self.spawn(NetworkService::Chain, &self.ckb_bin(), &args)?;
+            println!("{}", self.env(NetworkService::Chain)?);
+            return Ok(());
+        }
+
+        let mut child = Command::new(self.ckb_bin())
+            .args(&args)
+            .stdin(Stdio::null())
+            .spawn()
+            .map_err(|_| NetworkError::MissingBinary(self.ckb_bin()))?;
+        let pid_file = self.pid_file(NetworkService::Chain)?;
+        fs::write(&pid_file, child.id().to_string())?;
+        let status = child.wait()?;
+        fs::remove_file(&pid_file)?;
+        if !status.success() {
+            return Err(NetworkError::Io(std::io::Error::other(format!(
+                "ckb run exited with {}",
+                status
+            ))));
+        }
+        Ok(())
+    }
+
+    fn launch_indexer(&self) -> NetworkResult<()> {
+        if self.running_pid(NetworkService::Indexer)?.is_some() {
+            return Err(NetworkError::AlreadyRunning(NetworkService::Indexer));
+        }
+        let chain_url = NetworkService::Chain
+            .rpc_url(self.project)
+            .ok_or(NetworkError::MissingEnv)?;
+        let env = self.env(NetworkService::Indexer)?;
+        let args = vec![
+            "-c".to_string(),
+            chain_url,
+            "-l".to_string(),
+            format!("127.0.0.1:{}", env.host_port),
+            "-s".to_string(),
+            self.data_dir(NetworkService::Indexer)?
+                .display()
+                .to_string(),
+        ];
+        self.spawn(NetworkService::Indexer, &self.indexer_bin(), &args)?;
+        println!(
+            "Indexer RPC: {}",
+            NetworkService::Indexer.rpc_url(self.project).unwrap()
+        );
+        Ok(())
+    }
+
+    fn start_miner(&self) -> NetworkResult<()> {
+        if self.running_pid(NetworkService::Chain)?.is_none() {
+            return Err(NetworkError::NotLaunched(NetworkService::Chain));
+        }
+        if self.running_pid(NetworkService::Miner)?.is_some() {
+            return Err(NetworkError::MinerRunning);
+        }
+        let chain_dir = self.data_dir(NetworkService::Miner)?;
+        let args = self.chain_args(&chain_dir, &["miner"]);
+        self.spawn(NetworkService::Miner, &self.ckb_bin(), &args)?;
+        Ok(())
+    }
+
+    fn mine_blocks(&self, blocks: u64) -> NetworkResult<()> {
+        if self.running_pid(NetworkService::Chain)?.is_none() {
+            return Err(NetworkError::NotLaunched(NetworkService::Chain));
+        }
+        if self.running_pid(NetworkService::Miner)?.is_some() {
+            return Err(NetworkError::MinerRunning);
+        }
+        let chain_dir = self.data_dir(NetworkService::Miner)?;
+        let limit = blocks.to_string();
+        let status = Command::new(self.ckb_bin())
+            .args(self.chain_args(&chain_dir, &["miner", "--limit", limit.as_str()]))
+            .stdin(Stdio::null())
+            .status()
+            .map_err(|_| NetworkError::MissingBinary(self.ckb_bin()))?;
+        if !status.success() {
+            return Err(NetworkError::Io(std::io::Error::other(format!(
+                "ckb miner exited with {}",
+                status
+            ))));
+        }
+        Ok(())
+    }
+
+    fn restart(&self, service: NetworkService) -> NetworkResult<()> {
+        self.stop(Some(service))?;
+        self.start(service)
+    }
+
+    fn service_status(&self, service: NetworkService) -> NetworkResult<ServiceStatus> {
+        let pid = self.pid(service)?;
+        let program = self.program(service);
+        let running = pid.map(|pid| is_process_of(pid, &program)).unwrap_or(false);
+        let env = self.env(service)?;
+        let ports = match service {
+            NetworkService::Miner => vec![],
+            _ => vec![format!("{}:{}", env.host, env.host_port)],
+        };
+        Ok(ServiceStatus {
+            service,
+            instance: pid
+                .map(|pid| format!("pid {}", pid))
+                .unwrap_or_else(|| "-".to_string()),
+            running,
+            created: pid.is_some(),
+            state: match (pid, running) {
+                (None, _) => "not started".to_string(),
+                (Some(_), true) => "running".to_string(),
+                (Some(_), false) => "exited".to_string(),
+            },
+            ports,
+            data: vec![self
+                .project
+                .root_dir
+                .join(&env.local_binding)
+                .display()
+                .to_string()],
+        })
+    }
+
+    fn stop(&self, service: Option<NetworkService>) -> NetworkResult<()> {
+        let services = match service {
+            Some(service) => vec![service],
+            None => vec![
+                NetworkService::Indexer,
+                NetworkService::Miner,
+                NetworkService::Chain,
+            ],
+        };
+        for service in services {
+            if let Some(pid) = self.running_pid(service)? {
+                terminate(pid, &self.program(service))?;
+                println!("Stopped {} (pid {})", service, pid);
+            }
+            let pid_file = self.pid_file(service)?;
+            if pid_file.exists() {
+                fs::remove_file(pid_file)?;
+            }
+        }
+        Ok(())
+    }
+
+    // There is nothing besides the processes to tear down
+    fn down(&self) -> NetworkResult<()> {
+        self.stop(None)
+    }
+
+    fn logs(&self, service: NetworkService, options: &LogOptions) -> NetworkResult<()> {
+        let log_path = self.log_file(service)?;
+        if !log_path.exists() {
+            return Err(NetworkError::NotLaunched(service));
+        }
+        if options.since.is_some() {
+            eprintln!("Note: --since is not supported for native logs and will be ignored");
+        }
+        let mut file = fs::File::open(&log_path)?;
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        write!(stdout, "{}", tail_lines(&mut file, options.tail)?)?;
+        stdout.flush()?;
+        if !options.follow {
+            return Ok(());
+        }
+
+        // Poll for appended output, starting over if the file gets truncated
+        let mut position = file.seek(SeekFrom::End(0))?;
+        let mut buf = vec![];
+        loop {
+            thread::sleep(Duration::from_millis(500));
+            let len = fs::metadata(&log_path)?.len();
+            if len < position {
+                position = 0;
+            }
+            if len == position {
+                continue;
+            }
+            file.seek(SeekFrom::Start(position))?;
+            buf.clear();
+            file.read_to_end(&mut buf)?;
+            position += buf.len() as u64;
+            stdout.write_all(&buf)?;
+            stdout.flush()?;
+        }
+    }
+
+    fn recent_logs(&self, service: NetworkService, tail: usize) -> NetworkResult<String> {
+        let log_path = self.log_file(service)?;
+        if !log_path.exists() {
+            return Ok(String::new());
+        }
+        tail_lines(&mut fs::File::open(log_path)?, Some(tail))
+    }
+}
+
+// Reads the whole file, keeping only the last `tail` lines if given
+fn tail_lines(file: &mut fs::File, tail: Option<usize>) -> NetworkResult<String> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let lines = contents.lines().collect::<Vec<&str>>();
+    let skip = tail
+        .map(|tail| lines.len().saturating_sub(tail))
+        .unwrap_or(0);
+    Ok(lines[skip..]
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect())
+}
+
+// Whether `pid` is a live process running `program`. `ps` reports the executable
+// name, which Linux truncates to 15 characters
+fn is_process_of(pid: u32, program: &str) -> bool {
+    let output = match Command::new("ps")
+        .args(["-p", pid.to_string().as_str(), "-o", "comm="])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return false,
+    };
+    let file_name = |path: &str| {
+        Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let name = file_name(String::from_utf8_lossy(&output.stdout).trim());
+    let expected = file_name(program);
+    !name.is_empty() && (name == expected || (name.len() == 15 && expected.starts_with(&name)))
+}
+
+fn signal(pid: u32, signal: &str) -> NetworkResult<()> {
+    Command::new("kill")
+        .args([signal, pid.to_string().as_str()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    Ok(())
+}
+
+// Asks the process to shut down cleanly so ckb can flush its database,
+// and kills it if it is still around after `STOP_TIMEOUT`
+fn terminate(pid: u32, program: &str) -> NetworkResult<()> {
+    signal(pid, "-TERM")?;
+    let started = Instant::now();
+    while is_process_of(pid, program) {
+        if started.elapsed() > STOP_TIMEOUT {
+            signal(pid, "-KILL")?;
+            break;
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_process_of() {
+        let pid = std::process::id();
+        let exe = std::env::current_exe().unwrap();
+        assert!(is_process_of(pid, &exe.to_string_lossy()));
+        assert!(!is_process_of(pid, CKB_BIN));
+    }
+}
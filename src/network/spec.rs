@@ -0,0 +1,125 @@
+use super::{NetworkError, NetworkResult};
+use crate::account::AccountStore;
+use crate::project::{
+    ChainSpecConfig, IssuedCellConfig, LockConfig, TrampolineProject, DEFAULT_ENVIRONMENT,
+};
+
+use ckb_resource::{Resource, SPEC_DEV_FILE_NAME};
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+/// Where `ckb init --chain dev` expects the spec, relative to the chain directory.
+pub fn dev_spec_path(chain_dir: &Path) -> PathBuf {
+    chain_dir.join(SPEC_DEV_FILE_NAME)
+}
+
+/// Adds a genesis cell for each `dev` account when `fund_accounts` is set.
+pub fn with_project_accounts(
+    config: &ChainSpecConfig,
+    project: &TrampolineProject,
+) -> NetworkResult<ChainSpecConfig> {
+    let mut config = config.clone();
+    if !config.fund_accounts {
+        return Ok(config);
+    }
+    let environment = project.environment(DEFAULT_ENVIRONMENT)?;
+    for account in AccountStore::for_environment(project, &environment).list()? {
+        config.issued_cells.push(IssuedCellConfig {
+            capacity: config.fund_capacity(),
+            lock: LockConfig {
+                code_hash: LockConfig::SECP256K1_BLAKE160_CODE_HASH.to_string(),
+                hash_type: "type".to_string(),
+                args: format!("0x{}", hex::encode(account.lock_arg)),
+            },
+        });
+    }
+    Ok(config)
+}
+
+/// Renders ckb's bundled dev spec with the overrides from `[chain.spec]`.
+pub fn render_dev_spec(config: &ChainSpecConfig) -> NetworkResult<String> {
+    let bundled = Resource::bundled(SPEC_DEV_FILE_NAME.to_string()).get()?;
+    let mut spec = String::from_utf8_lossy(&bundled).parse::<Value>()?;
+
+    let params = table(&mut spec, "params")?;
+    if let Some(target) = config.epoch_duration_target {
+        params.insert("epoch_duration_target".into(), integer(target)?);
+    }
+    if let Some(length) = config.genesis_epoch_length {
+        params.insert("genesis_epoch_length".into(), integer(length)?);
+    }
+    if let Some(permanent) = config.permanent_difficulty_in_dummy {
+        params.insert(
+            "permanent_difficulty_in_dummy".into(),
+            Value::Boolean(permanent),
+        );
+    }
+    if let Some(maturity) = config.cellbase_maturity {
+        params.insert("cellbase_maturity".into(), integer(maturity)?);
+    }
+
+    if !config.issued_cells.is_empty() {
+        let genesis = table(&mut spec, "genesis")?;
+        let issued_cells = genesis
+            .entry("issued_cells")
+            .or_insert_with(|| Value::Array(vec![]))
+            .as_array_mut()
+            .ok_or_else(|| NetworkError::Spec("genesis.issued_cells is not an array".into()))?;
+        for cell in &config.issued_cells {
+            issued_cells.push(Value::try_from(cell)?);
+        }
+    }
+
+    Ok(toml::to_string(&spec)?)
+}
+
+fn table<'a>(spec: &'a mut Value, key: &str) -> NetworkResult<&'a mut toml::value::Table> {
+    spec.get_mut(key)
+        .and_then(Value::as_table_mut)
+        .ok_or_else(|| NetworkError::Spec(format!("missing [{}] table", key)))
+}
+
+// TOML integers are signed
+fn integer(value: u64) -> NetworkResult<Value> {
+    i64::try_from(value)
+        .map(Value::Integer)
+        .map_err(|_| NetworkError::Spec(format!("{} does not fit in a TOML integer", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::{IssuedCellConfig, LockConfig};
+
+    #[test]
+    fn test_render_dev_spec_overrides() {
+        let config = ChainSpecConfig {
+            issued_cells: vec![IssuedCellConfig {
+                capacity: 100_000_000_000_000,
+                lock: LockConfig {
+                    code_hash: LockConfig::SECP256K1_BLAKE160_CODE_HASH.to_string(),
+                    hash_type: "type".to_string(),
+                    args: "0x0000000000000000000000000000000000000001".to_string(),
+                },
+            }],
+            genesis_epoch_length: Some(10),
+            cellbase_maturity: Some(0),
+            ..Default::default()
+        };
+        let spec = render_dev_spec(&config).unwrap().parse::<Value>().unwrap();
+
+        assert_eq!(
+            spec["params"]["genesis_epoch_length"].as_integer(),
+            Some(10)
+        );
+        assert_eq!(
+            spec["params"]["epoch_duration_target"].as_integer(),
+            Some(14400)
+        );
+        let issued_cells = spec["genesis"]["issued_cells"].as_array().unwrap();
+        assert_eq!(
+            issued_cells.last().unwrap()["lock"]["args"].as_str(),
+            Some("0x0000000000000000000000000000000000000001")
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::network::NetworkService;
+use crate::project::DEFAULT_ENVIRONMENT;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -7,6 +9,20 @@ use structopt::StructOpt;
     name = "trampoline",
     alias = "tram"
 )]
+pub struct TrampolineOpts {
+    #[structopt(
+        long,
+        global = true,
+        default_value = DEFAULT_ENVIRONMENT,
+        env = "TRAMPOLINE_ENV",
+        help = "Environment from trampoline.toml whose endpoints and accounts are used"
+    )]
+    pub env: String,
+    #[structopt(subcommand)]
+    pub command: TrampolineCommand,
+}
+
+#[derive(Debug, StructOpt)]
 pub enum TrampolineCommand {
     #[structopt(name = "new", alias = "n")]
     #[structopt(about = "Create a new Trampoline project")]
@@ -29,11 +45,183 @@ pub enum TrampolineCommand {
         #[structopt(flatten)]
         command: SchemaCommand,
     },
-    // #[structopt(name = "account", about = "Manage addresses and keys")]
-    // Account {
-    //     #[structopt(flatten)]
-    //     command: AccountCommand
-    // }
+    #[structopt(
+        name = "address",
+        about = "Decode a CKB address into its lock script, or encode a lock arg as an address"
+    )]
+    Address {
+        #[structopt(required_unless = "lock-arg")]
+        address: Option<String>,
+        #[structopt(
+            long,
+            conflicts_with = "address",
+            help = "Lock arg of a secp256k1-blake160 lock, encoded for the network of `--env`"
+        )]
+        lock_arg: Option<String>,
+    },
+    #[structopt(name = "account", about = "Manage addresses and keys")]
+    Account {
+        #[structopt(flatten)]
+        command: AccountCommand,
+    },
+    #[structopt(name = "tx", about = "Work with transactions")]
+    Tx {
+        #[structopt(flatten)]
+        command: TxCommand,
+    },
+    #[structopt(
+        name = "generate",
+        alias = "gen",
+        about = "Build an unsigned transaction from a pattern in `generators/`"
+    )]
+    Generate {
+        #[structopt(help = "Pattern name, the file name in `generators/` without `.toml`")]
+        pattern: String,
+        #[structopt(
+            long = "param",
+            short,
+            parse(try_from_str = parse_param),
+            help = "Value of a pattern parameter as key=value. Repeat for every parameter"
+        )]
+        params: Vec<(String, String)>,
+        #[structopt(
+            long,
+            short,
+            help = "Write the transaction to this file instead of stdout"
+        )]
+        output: Option<PathBuf>,
+    },
+    #[structopt(
+        name = "pattern",
+        about = "Work with the transaction patterns in `generators/`"
+    )]
+    Pattern {
+        #[structopt(subcommand)]
+        command: PatternCommand,
+    },
+    #[structopt(name = "script", about = "Manage the contracts in `scripts/`")]
+    Script {
+        #[structopt(subcommand)]
+        command: ScriptCommand,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ScriptCommand {
+    #[structopt(
+        name = "new",
+        about = "Create a ckb-std contract crate in `scripts/<name>`"
+    )]
+    New { name: String },
+    #[structopt(
+        name = "build",
+        about = "Compile contracts for CKB-VM into `build/<profile>`, printing their code hashes"
+    )]
+    Build {
+        #[structopt(help = "Scripts to build. Builds every crate in `scripts/` when omitted")]
+        names: Vec<String>,
+        #[structopt(long, help = "Build with the release profile")]
+        release: bool,
+    },
+    #[structopt(
+        name = "deploy",
+        about = "Put a built script in a cell on the local chain and record it under [deployed]"
+    )]
+    Deploy {
+        name: String,
+        #[structopt(
+            long,
+            help = "Create the cell under a Type ID so the script can be upgraded in place"
+        )]
+        type_id: bool,
+        #[structopt(long, help = "Deploy the release build instead of the debug one")]
+        release: bool,
+        #[structopt(
+            long,
+            help = "Account paying for the cell. Defaults to the dev chain's genesis account"
+        )]
+        account: Option<String>,
+        #[structopt(
+            long,
+            default_value = "120",
+            help = "Seconds to wait for the transaction to be committed"
+        )]
+        timeout: u64,
+    },
+    #[structopt(
+        name = "upgrade",
+        about = "Replace the binary of a script deployed with --type-id, keeping its type hash"
+    )]
+    Upgrade {
+        name: String,
+        #[structopt(long, help = "Deploy the release build instead of the debug one")]
+        release: bool,
+        #[structopt(
+            long,
+            conflicts_with = "to",
+            help = "Restore the binary of the version before the current one"
+        )]
+        rollback: bool,
+        #[structopt(
+            long,
+            help = "Restore the binary of a previous version, by data hash or a prefix of it"
+        )]
+        to: Option<String>,
+        #[structopt(
+            long,
+            help = "Account paying for the cell. Defaults to the dev chain's genesis account"
+        )]
+        account: Option<String>,
+        #[structopt(
+            long,
+            default_value = "120",
+            help = "Seconds to wait for the transaction to be committed"
+        )]
+        timeout: u64,
+    },
+    #[structopt(
+        name = "test",
+        about = "Build every script and run the tests crate against the binaries, offline"
+    )]
+    Test {
+        #[structopt(long, help = "Test the release builds")]
+        release: bool,
+        #[structopt(
+            long,
+            help = "Test the binaries already in `build/` without rebuilding"
+        )]
+        no_build: bool,
+        #[structopt(last = true, help = "Arguments passed on to `cargo test`")]
+        cargo_args: Vec<String>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum PatternCommand {
+    #[structopt(
+        name = "compile",
+        about = "Emit a script crate under `scripts/` that checks transactions against a pattern"
+    )]
+    Compile {
+        #[structopt(help = "Pattern name, the file name in `generators/` without `.toml`")]
+        pattern: String,
+        #[structopt(long, help = "Name of the script crate, defaults to the pattern name")]
+        name: Option<String>,
+        #[structopt(
+            long = "param",
+            short,
+            parse(try_from_str = parse_param),
+            help = "Value of a pattern parameter as key=value. Repeat for every parameter"
+        )]
+        params: Vec<(String, String)>,
+    },
+}
+
+fn parse_param(param: &str) -> Result<(String, String), String> {
+    param
+        .split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected key=value, got {}", param))
 }
 
 #[derive(Debug, StructOpt)]
@@ -43,16 +231,38 @@ pub enum NetworkCommands {
         about = "Launch local development network",
         alias = "l"
     )]
-    Launch {},
+    Launch {
+        #[structopt(
+            long,
+            short,
+            help = "Run the node in the foreground with its output attached"
+        )]
+        attach: bool,
+        #[structopt(
+            long,
+            default_value = "60",
+            help = "Seconds to wait for the node RPC to answer before giving up"
+        )]
+        timeout: u64,
+        #[structopt(long, help = "Return without waiting for the node to be ready")]
+        no_wait: bool,
+    },
     #[structopt(
         name = "set-miner",
         about = "Set the miner address so blocks can be mined locally"
     )]
     SetMiner {
-        #[structopt(name = "pubkey", required_unless = "lock_arg", long)]
+        #[structopt(name = "pubkey", required_unless_one = &["lock_arg", "address"], long)]
         pubkey: Option<String>,
-        #[structopt(name = "lock_arg", required_unless = "pubkey", long)]
+        #[structopt(name = "lock_arg", required_unless_one = &["pubkey", "address"], long)]
         lock_arg: Option<String>,
+        #[structopt(
+            name = "address",
+            long,
+            conflicts_with_all = &["pubkey", "lock_arg"],
+            help = "CKB address (ckt1... or ckb1...) whose lock script receives the block rewards"
+        )]
+        address: Option<String>,
     },
     #[structopt(
         name = "config",
@@ -60,25 +270,107 @@ pub enum NetworkCommands {
         about = "Configure your local developer network. You can also manually edit `trampoline-env.toml`"
     )]
     Config {
-        #[structopt(name = "host-port", long, short)]
+        #[structopt(
+            default_value = "chain",
+            help = "Service to configure: chain, miner or indexer"
+        )]
+        service: NetworkService,
+        #[structopt(name = "host-port", long, short = "p")]
         port_host: Option<usize>,
-        #[structopt(name = "host", long, short)]
+        #[structopt(name = "host", long)]
         host: Option<String>,
         #[structopt(name = "local-path-binding", long, short)]
         local_binding: Option<PathBuf>,
+        #[structopt(long, help = "Print the resolved configuration of every service")]
+        show: bool,
+    },
+    #[structopt(
+        name = "indexer",
+        alias = "index",
+        about = "Launch the indexer for improved queries"
+    )]
+    Indexer {
+        #[structopt(
+            long,
+            default_value = "60",
+            help = "Seconds to wait for the indexer RPC to answer before giving up"
+        )]
+        timeout: u64,
+        #[structopt(long, help = "Return without waiting for the indexer to be ready")]
+        no_wait: bool,
     },
-    #[structopt(name = "index", about = "Launch the indexer for improved queries")]
-    Indexer {},
     #[structopt(
         name = "miner",
-        about = "Start continuously mining blocks or mine a single block"
+        about = "Start continuously mining blocks or mine a fixed number of blocks"
     )]
-    Miner { one_block: Option<bool> },
+    Miner {
+        #[structopt(
+            long,
+            conflicts_with_all = &["until-epoch", "stop"],
+            help = "Mine exactly this many blocks, then exit"
+        )]
+        blocks: Option<u64>,
+        #[structopt(
+            name = "until-epoch",
+            long,
+            conflicts_with = "stop",
+            help = "Mine until the chain reaches the start of this epoch, then exit"
+        )]
+        until_epoch: Option<u64>,
+        #[structopt(long, help = "Stop a running miner")]
+        stop: bool,
+    },
     #[structopt(
         name = "init",
-        about = "Initialize new network configuration without starting"
+        about = "Initialize new network configuration without starting. Renders `[chain.spec]` into the node's dev spec"
     )]
-    Init {},
+    Init {
+        #[structopt(
+            long,
+            help = "Delete existing chain and indexer data so a changed genesis can take effect"
+        )]
+        reset: bool,
+    },
+    #[structopt(
+        name = "stop",
+        about = "Stop the chain, miner and indexer, or only the given service"
+    )]
+    Stop {
+        #[structopt(name = "service", help = "One of: chain, miner, indexer")]
+        service: Option<NetworkService>,
+    },
+    #[structopt(
+        name = "status",
+        alias = "ps",
+        about = "Show whether each service is running, with its ports and data directory"
+    )]
+    Status {},
+    #[structopt(
+        name = "down",
+        about = "Stop and remove all containers and the docker network of this project"
+    )]
+    Down {},
+    #[structopt(
+        name = "logs",
+        about = "Show the output of the chain, miner or indexer"
+    )]
+    Logs {
+        #[structopt(
+            name = "service",
+            default_value = "chain",
+            help = "One of: chain, miner, indexer"
+        )]
+        service: NetworkService,
+        #[structopt(long, short, help = "Keep streaming new output")]
+        follow: bool,
+        #[structopt(
+            long,
+            help = "Only show output since a UNIX timestamp or a duration, e.g. 10m"
+        )]
+        since: Option<String>,
+        #[structopt(long, short = "n", help = "Number of lines to show from the end")]
+        tail: Option<usize>,
+    },
 }
 #[derive(Debug, StructOpt)]
 pub enum SchemaCommand {
@@ -87,3 +379,95 @@ pub enum SchemaCommand {
     #[structopt(name = "build", about = "Generate rust bindings for schema")]
     Build { name: String },
 }
+
+#[derive(Debug, StructOpt)]
+pub enum AccountCommand {
+    #[structopt(
+        name = "new",
+        about = "Generate a new key, stored encrypted under `.trampoline/accounts`"
+    )]
+    New { name: String },
+    #[structopt(
+        name = "list",
+        alias = "ls",
+        about = "List accounts with their lock args and addresses"
+    )]
+    List {},
+    #[structopt(
+        name = "import",
+        about = "Import a hex private key or an existing keystore file"
+    )]
+    Import {
+        name: String,
+        #[structopt(
+            long,
+            required_unless = "keystore",
+            conflicts_with = "keystore",
+            help = "File whose first line is a hex private key, as written by `ckb-cli account export`"
+        )]
+        privkey_path: Option<PathBuf>,
+        #[structopt(long, help = "Keystore JSON file")]
+        keystore: Option<PathBuf>,
+    },
+    #[structopt(name = "export", about = "Print or save the private key of an account")]
+    Export {
+        name: String,
+        #[structopt(long, short, help = "Write to this file instead of stdout")]
+        output: Option<PathBuf>,
+        #[structopt(long, help = "Export the encrypted keystore instead of the raw key")]
+        keystore: bool,
+    },
+    #[structopt(
+        name = "fund",
+        about = "Send CKB from the dev chain's genesis account to an account or address"
+    )]
+    Fund {
+        #[structopt(help = "Account name or address")]
+        target: String,
+        #[structopt(long, default_value = "10000", help = "Amount in CKB")]
+        amount: u64,
+    },
+    #[structopt(name = "remove", alias = "rm", about = "Delete an account")]
+    Remove {
+        name: String,
+        #[structopt(long, short, help = "Do not ask for confirmation")]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum TxCommand {
+    #[structopt(
+        name = "sign",
+        about = "Sign the inputs of a JSON transaction that an account can unlock"
+    )]
+    Sign {
+        #[structopt(help = "Transaction in the JSON format of the `send_transaction` RPC")]
+        file: PathBuf,
+        #[structopt(long, help = "Account whose key signs")]
+        account: String,
+        #[structopt(
+            long,
+            short,
+            help = "Write the signed transaction to this file instead of back to <file>"
+        )]
+        output: Option<PathBuf>,
+        #[structopt(
+            long = "multisig-key",
+            help = "Lock arg of a multisig member, in order. Repeat for every member"
+        )]
+        multisig_keys: Vec<String>,
+        #[structopt(
+            long,
+            default_value = "1",
+            help = "Signatures required by the multisig lock"
+        )]
+        threshold: u8,
+        #[structopt(
+            long,
+            default_value = "0",
+            help = "Leading multisig members who must always sign"
+        )]
+        require_first_n: u8,
+    },
+}
@@ -0,0 +1,446 @@
+mod compile;
+
+use crate::account::{AccountError, AccountStore};
+use crate::address::Address;
+use crate::indexer::{
+    BlockingIndexerClient, IndexerClient, IndexerError, Order, SearchKey, SearchKeyFilter,
+};
+use crate::project::{Environment, TrampolineProject, TRAMPOLINE_SCHEMAS};
+use crate::rpc::{BlockingRpcClient, RpcClient, RpcError};
+use crate::schema::{self, SchemaError};
+use crate::transaction::{TransactionBuilder, TransactionError};
+
+use ckb_jsonrpc_types::{DepType, JsonBytes, Script, ScriptHashType};
+use ckb_types::{
+    bytes::Bytes,
+    core::{self, Capacity, TransactionView},
+    packed,
+    prelude::*,
+    H256,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use tera::{Context as TeraContext, Tera};
+use thiserror::Error;
+
+pub const GENERATORS_FOLDER: &str = "generators";
+
+const SHANNONS_PER_CKB: u64 = 100_000_000;
+
+#[derive(Debug, Error)]
+pub enum PatternError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Tera(#[from] tera::Error),
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+    #[error(transparent)]
+    Account(#[from] AccountError),
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    #[error(transparent)]
+    Indexer(#[from] IndexerError),
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+    #[error("No pattern {0} in {}", GENERATORS_FOLDER)]
+    NotFound(String),
+    #[error("Invalid pattern: {0}")]
+    Invalid(String),
+}
+
+pub type PatternResult<T> = std::result::Result<T, PatternError>;
+
+/// A transaction described in `generators/<name>.toml`. The file is rendered as a
+/// template with the `--param` values of `trampoline generate` before it is parsed.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Pattern {
+    pub description: Option<String>,
+    // Accounts or addresses whose plain cells pay for the outputs and the fee
+    #[serde(default)]
+    pub from: Vec<String>,
+    // Receives the change, defaulting to the first of `from`
+    pub change: Option<String>,
+    // Shannons per 1000 bytes
+    pub fee_rate: Option<u64>,
+    #[serde(default)]
+    pub cell_deps: Vec<CellDepSpec>,
+    #[serde(default)]
+    pub inputs: Vec<InputSpec>,
+    #[serde(default)]
+    pub outputs: Vec<OutputSpec>,
+    // How the total capacity of the inputs compares to that of the outputs
+    pub input_capacity: Option<Comparator>,
+}
+
+/// A script recorded under `[deployed]` by `trampoline script deploy`, or an out point.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum CellDepSpec {
+    Deployed {
+        script: String,
+    },
+    OutPoint {
+        tx_hash: H256,
+        #[serde(default)]
+        index: u32,
+        #[serde(default = "default_dep_type")]
+        dep_type: DepType,
+    },
+}
+
+/// A specific cell when `tx_hash` is set, otherwise the first `limit` live cells
+/// with the given type script and, or, lock.
+#[derive(Deserialize, Debug, Clone)]
+pub struct InputSpec {
+    pub tx_hash: Option<H256>,
+    #[serde(default)]
+    pub index: u32,
+    pub lock: Option<LockSpec>,
+    #[serde(rename = "type")]
+    pub type_: Option<ScriptSpec>,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OutputSpec {
+    // Defaults to the minimum the cell occupies, raised to `min_capacity`
+    pub capacity: Option<CapacitySpec>,
+    pub min_capacity: Option<CapacitySpec>,
+    pub max_capacity: Option<CapacitySpec>,
+    pub lock: LockSpec,
+    #[serde(rename = "type")]
+    pub type_: Option<ScriptSpec>,
+    pub data: Option<DataSpec>,
+}
+
+/// An account name, an address or a full script.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum LockSpec {
+    Name(String),
+    Script(ScriptSpec),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScriptSpec {
+    pub code_hash: H256,
+    #[serde(default = "default_hash_type")]
+    pub hash_type: ScriptHashType,
+    #[serde(default)]
+    pub args: JsonBytes,
+}
+
+/// CKB as an integer or a decimal string.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum CapacitySpec {
+    Ckb(u64),
+    Text(String),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparator {
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = "<")]
+    Lt,
+}
+
+/// Raw hex data, or a value encoded as a type of `schemas/mol/<schema>.mol`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum DataSpec {
+    Hex(JsonBytes),
+    Schema {
+        schema: String,
+        #[serde(rename = "type")]
+        type_name: String,
+        value: toml::Value,
+    },
+}
+
+fn default_dep_type() -> DepType {
+    DepType::Code
+}
+
+fn default_hash_type() -> ScriptHashType {
+    ScriptHashType::Type
+}
+
+fn default_limit() -> usize {
+    1
+}
+
+impl From<ScriptSpec> for packed::Script {
+    fn from(spec: ScriptSpec) -> Self {
+        Script {
+            code_hash: spec.code_hash,
+            hash_type: spec.hash_type,
+            args: spec.args,
+        }
+        .into()
+    }
+}
+
+impl CapacitySpec {
+    pub fn shannons(&self) -> PatternResult<u64> {
+        let invalid = || PatternError::Invalid(format!("invalid capacity {:?}", self));
+        match self {
+            CapacitySpec::Ckb(ckb) => ckb.checked_mul(SHANNONS_PER_CKB).ok_or_else(invalid),
+            CapacitySpec::Text(text) => {
+                let (whole, fraction) = text.trim().split_once('.').unwrap_or((text.trim(), ""));
+                if fraction.len() > 8 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                let whole = whole.parse::<u64>().map_err(|_| invalid())?;
+                let fraction = format!("{:0<8}", fraction).parse::<u64>().unwrap_or(0);
+                whole
+                    .checked_mul(SHANNONS_PER_CKB)
+                    .and_then(|shannons| shannons.checked_add(fraction))
+                    .ok_or_else(invalid)
+            }
+        }
+    }
+}
+
+impl Pattern {
+    pub fn path(project: &TrampolineProject, name: &str) -> PathBuf {
+        project
+            .root_dir
+            .join(GENERATORS_FOLDER)
+            .join(format!("{}.toml", name))
+    }
+
+    pub fn load(
+        project: &TrampolineProject,
+        name: &str,
+        params: &BTreeMap<String, String>,
+    ) -> PatternResult<Self> {
+        let path = Self::path(project, name);
+        if !path.exists() {
+            return Err(PatternError::NotFound(name.to_string()));
+        }
+        Self::render(&fs::read_to_string(path)?, params)
+    }
+
+    pub fn render(source: &str, params: &BTreeMap<String, String>) -> PatternResult<Self> {
+        let mut context = TeraContext::new();
+        for (key, value) in params {
+            context.insert(key, value);
+        }
+        let rendered = Tera::one_off(source, &context, false)?;
+        Ok(toml::from_str(&rendered)?)
+    }
+
+    /// Resolves the pattern against `environment` into a balanced, unsigned transaction.
+    pub fn generate(
+        &self,
+        project: &TrampolineProject,
+        environment: &Environment,
+    ) -> PatternResult<TransactionView> {
+        let rpc = BlockingRpcClient::new(RpcClient::new(&environment.ckb_rpc))?;
+        let indexer = BlockingIndexerClient::new(IndexerClient::new(&environment.indexer_rpc))?;
+        let store = AccountStore::for_environment(project, environment);
+        let lock = |spec: &LockSpec| -> PatternResult<packed::Script> {
+            match spec {
+                LockSpec::Script(script) => Ok(script.clone().into()),
+                LockSpec::Name(name) => match Address::decode(name) {
+                    Ok(address) => Ok(address.script().clone()),
+                    Err(_) => Ok(store.get(name)?.lock_script()),
+                },
+            }
+        };
+
+        let mut builder = TransactionBuilder::new(&rpc, &indexer);
+        for sender in &self.from {
+            builder = builder.from_lock(lock(&LockSpec::Name(sender.clone()))?);
+        }
+        if let Some(change) = &self.change {
+            builder = builder.change_lock(lock(&LockSpec::Name(change.clone()))?);
+        }
+        if let Some(fee_rate) = self.fee_rate {
+            builder = builder.fee_rate(fee_rate);
+        }
+        for cell_dep in &self.cell_deps {
+            let cell_dep = match cell_dep {
+                CellDepSpec::Deployed { script } => project
+                    .deployed(script)
+                    .ok_or_else(|| {
+                        PatternError::Invalid(format!(
+                            "script {} is not deployed. Run `trampoline script deploy {}`",
+                            script, script
+                        ))
+                    })?
+                    .cell_dep(),
+                CellDepSpec::OutPoint {
+                    tx_hash,
+                    index,
+                    dep_type,
+                } => packed::CellDep::new_builder()
+                    .out_point(packed::OutPoint::new(tx_hash.pack(), *index))
+                    .dep_type(core::DepType::from(dep_type.clone()).into())
+                    .build(),
+            };
+            builder = builder.cell_dep(cell_dep);
+        }
+
+        for (index, input) in self.inputs.iter().enumerate() {
+            if let Some(tx_hash) = &input.tx_hash {
+                builder = builder.input(packed::OutPoint::new(tx_hash.pack(), input.index));
+                continue;
+            }
+            let input_lock = input.lock.as_ref().map(&lock).transpose()?;
+            let key = match (&input.type_, input_lock) {
+                (Some(type_), input_lock) => SearchKey::type_script(
+                    packed::Script::from(type_.clone()).into(),
+                )
+                .filter(SearchKeyFilter {
+                    script: input_lock.map(Into::into),
+                    ..Default::default()
+                }),
+                (None, Some(input_lock)) => SearchKey::lock(input_lock.into()),
+                (None, None) => {
+                    return Err(PatternError::Invalid(format!(
+                        "input {} needs a tx_hash, a lock or a type",
+                        index
+                    )))
+                }
+            };
+            let cells = indexer
+                .cells(key, Order::Asc)
+                .take(input.limit)
+                .collect::<Result<Vec<_>, _>>()?;
+            if cells.len() < input.limit {
+                return Err(PatternError::Invalid(format!(
+                    "input {} matches {} live cells but needs {}",
+                    index,
+                    cells.len(),
+                    input.limit
+                )));
+            }
+            for cell in cells {
+                builder = builder.input(cell.out_point.into());
+            }
+        }
+
+        for (index, output) in self.outputs.iter().enumerate() {
+            let data = match &output.data {
+                None => Bytes::new(),
+                Some(DataSpec::Hex(data)) => data.clone().into_bytes(),
+                Some(DataSpec::Schema {
+                    schema,
+                    type_name,
+                    value,
+                }) => {
+                    let path = schema_path(project, schema);
+                    Bytes::from(schema::encode(&path, type_name, value)?)
+                }
+            };
+            let cell = packed::CellOutput::new_builder()
+                .lock(lock(&output.lock)?)
+                .type_(output.type_.clone().map(packed::Script::from).pack())
+                .build();
+            let min_capacity = shannons(&output.min_capacity)?;
+            let max_capacity = shannons(&output.max_capacity)?;
+            let capacity = match shannons(&output.capacity)? {
+                Some(capacity) => capacity,
+                None => cell
+                    .occupied_capacity(Capacity::bytes(data.len()).map_err(TransactionError::from)?)
+                    .map_err(TransactionError::from)?
+                    .as_u64()
+                    .max(min_capacity.unwrap_or(0)),
+            };
+            if min_capacity.is_some_and(|min| capacity < min)
+                || max_capacity.is_some_and(|max| capacity > max)
+            {
+                return Err(PatternError::Invalid(format!(
+                    "output {} capacity of {} shannons is out of its bounds",
+                    index, capacity
+                )));
+            }
+            let capacity = Capacity::shannons(capacity);
+            builder = builder.output(cell.as_builder().capacity(capacity.pack()).build(), data);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+fn shannons(capacity: &Option<CapacitySpec>) -> PatternResult<Option<u64>> {
+    capacity.as_ref().map(CapacitySpec::shannons).transpose()
+}
+
+fn schema_path(project: &TrampolineProject, schema: &str) -> PathBuf {
+    project
+        .root_dir
+        .join(TRAMPOLINE_SCHEMAS)
+        .join("mol")
+        .join(format!("{}.mol", schema))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSFER: &str = r#"
+description = "Send CKB"
+from = ["{{ sender }}"]
+
+[[outputs]]
+capacity = "{{ amount }}"
+lock = "{{ recipient }}"
+
+[[outputs]]
+min_capacity = 200
+lock = { code_hash = "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8", args = "0x01" }
+data = { schema = "token", type = "Token", value = { symbol = "TKN" } }
+"#;
+
+    #[test]
+    fn test_render_pattern() {
+        let params = [
+            ("sender", "alice"),
+            ("recipient", "bob"),
+            ("amount", "61.5"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        let pattern = Pattern::render(TRANSFER, &params).unwrap();
+
+        assert_eq!(pattern.from, vec!["alice".to_string()]);
+        let transfer = &pattern.outputs[0];
+        assert!(matches!(&transfer.lock, LockSpec::Name(name) if name == "bob"));
+        assert_eq!(
+            transfer.capacity.as_ref().unwrap().shannons().unwrap(),
+            6_150_000_000
+        );
+        let token = &pattern.outputs[1];
+        assert!(token.capacity.is_none());
+        assert_eq!(
+            token.min_capacity.as_ref().unwrap().shannons().unwrap(),
+            20_000_000_000
+        );
+        assert_eq!(pattern.input_capacity, None);
+        assert!(
+            matches!(&token.lock, LockSpec::Script(script) if script.hash_type == ScriptHashType::Type)
+        );
+        assert!(
+            matches!(&token.data, Some(DataSpec::Schema { type_name, .. }) if type_name == "Token")
+        );
+
+        assert!(Pattern::render(TRANSFER, &BTreeMap::new()).is_err());
+    }
+}
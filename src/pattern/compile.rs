@@ -0,0 +1,221 @@
+use super::{
+    schema_path, shannons, Comparator, DataSpec, LockSpec, Pattern, PatternError, PatternResult,
+};
+use crate::project::TrampolineProject;
+use crate::schema::gen_bindings;
+use crate::script::SCRIPTS_FOLDER;
+use crate::TEMPLATES;
+
+use ckb_types::packed;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use tera::Context as TeraContext;
+
+// Rendered into the generated crate, relative to its root
+const CRATE_TEMPLATES: [&str; 2] = ["Cargo.toml", "src/main.rs"];
+
+#[derive(Serialize)]
+struct InputCheck {
+    index: usize,
+    count: usize,
+    type_hash: String,
+}
+
+#[derive(Serialize)]
+struct OutputCheck {
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    capacity: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_capacity: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_capacity: Option<u64>,
+    lock_hash: Option<String>,
+    type_hash: Option<String>,
+    data: Option<String>,
+    // Molecule reader the data must verify as
+    reader: Option<String>,
+}
+
+impl Pattern {
+    /// Writes a `no_std` script crate to `scripts/<name>` that accepts transactions shaped
+    /// like this pattern: the number of outputs (plus an optional change output), the
+    /// inputs selected by type script, how the input capacity compares to the output
+    /// capacity, and for each output its capacity or bounds, type script, data and any
+    /// lock given as a full script. Account and address locks are not
+    /// checked since they usually vary between transactions. Schema-typed data is
+    /// verified with bindings generated from `schemas/mol`.
+    pub fn compile(
+        &self,
+        project: &TrampolineProject,
+        pattern: &str,
+        name: &str,
+    ) -> PatternResult<PathBuf> {
+        let crate_dir = project.root_dir.join(SCRIPTS_FOLDER).join(name);
+        fs::create_dir_all(crate_dir.join("src"))?;
+
+        let inputs = self
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| input.tx_hash.is_none())
+            .filter_map(|(index, input)| {
+                Some(InputCheck {
+                    index,
+                    count: input.limit,
+                    type_hash: script_hash(input.type_.clone()?.into()),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut schemas: Vec<&str> = vec![];
+        let mut outputs = vec![];
+        for (index, output) in self.outputs.iter().enumerate() {
+            let (data, reader) = match &output.data {
+                None => (None, None),
+                Some(DataSpec::Hex(data)) => (Some(byte_array(data.as_bytes())), None),
+                Some(DataSpec::Schema {
+                    schema, type_name, ..
+                }) => {
+                    if !schema
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Err(PatternError::Invalid(format!(
+                            "schema {} is not a valid module name",
+                            schema
+                        )));
+                    }
+                    if !schemas.contains(&schema.as_str()) {
+                        schemas.push(schema.as_str());
+                    }
+                    (None, Some(format!("{}::{}Reader", schema, type_name)))
+                }
+            };
+            outputs.push(OutputCheck {
+                index,
+                capacity: shannons(&output.capacity)?,
+                min_capacity: shannons(&output.min_capacity)?,
+                max_capacity: shannons(&output.max_capacity)?,
+                lock_hash: match &output.lock {
+                    LockSpec::Script(script) => Some(script_hash(script.clone().into())),
+                    LockSpec::Name(_) => None,
+                },
+                type_hash: output
+                    .type_
+                    .clone()
+                    .map(|script| script_hash(script.into())),
+                data,
+                reader,
+            });
+        }
+
+        for schema in &schemas {
+            let path = schema_path(project, schema);
+            if !path.exists() {
+                return Err(PatternError::Invalid(format!(
+                    "schema {} not found at {}",
+                    schema,
+                    path.display()
+                )));
+            }
+            gen_bindings(path, crate_dir.join("src"))?;
+        }
+
+        let mut context = TeraContext::new();
+        context.insert("pattern", pattern);
+        context.insert("name", name);
+        context.insert("schemas", &schemas);
+        context.insert("inputs", &inputs);
+        context.insert("outputs", &outputs);
+        if let Some(comparator) = self.input_capacity {
+            context.insert("input_capacity", operator(comparator));
+        }
+        for template in CRATE_TEMPLATES {
+            let content = TEMPLATES.render(&format!("pattern/{}", template), &context)?;
+            fs::write(crate_dir.join(template), content)?;
+        }
+        Ok(crate_dir)
+    }
+}
+
+fn operator(comparator: Comparator) -> &'static str {
+    match comparator {
+        Comparator::Eq => "==",
+        Comparator::Ge => ">=",
+        Comparator::Gt => ">",
+        Comparator::Le => "<=",
+        Comparator::Lt => "<",
+    }
+}
+
+fn script_hash(script: packed::Script) -> String {
+    byte_array(&script.calc_script_hash().raw_data())
+}
+
+// Rust array literal of `bytes`
+fn byte_array(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "[0u8; 0]".to_string();
+    }
+    let items = bytes
+        .iter()
+        .map(|byte| format!("{:#04x}", byte))
+        .collect::<Vec<_>>();
+    format!("[{}]", items.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    const PATTERN: &str = r#"
+input_capacity = ">="
+
+[[inputs]]
+type = { code_hash = "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8", args = "0x01" }
+limit = 2
+
+[[outputs]]
+capacity = 100
+lock = "alice"
+data = "0x0102"
+
+[[outputs]]
+capacity = 0
+lock = "bob"
+
+[[outputs]]
+min_capacity = 61
+max_capacity = "70.5"
+lock = "bob"
+"#;
+
+    #[test]
+    fn test_compile_checks() {
+        let root_dir = std::env::temp_dir().join(format!("trampoline-{}", std::process::id()));
+        let project = TrampolineProject {
+            root_dir: root_dir.clone(),
+            ..Default::default()
+        };
+        let pattern = Pattern::render(PATTERN, &BTreeMap::new()).unwrap();
+        let crate_dir = pattern.compile(&project, "mint", "mint-check").unwrap();
+        let main = fs::read_to_string(crate_dir.join("src/main.rs")).unwrap();
+        let manifest = fs::read_to_string(crate_dir.join("Cargo.toml")).unwrap();
+        fs::remove_dir_all(&root_dir).unwrap();
+
+        assert!(manifest.contains("name = \"mint-check\""));
+        assert!(main.contains("const OUTPUTS: usize = 3;"));
+        assert!(main.contains("if matching < 2 {"));
+        assert!(main.contains("!= 10000000000 {"));
+        assert!(main.contains("load_cell_capacity(1, Source::Output)? != 0 {"));
+        assert!(main.contains("load_cell_capacity(2, Source::Output)? < 6100000000 {"));
+        assert!(main.contains("load_cell_capacity(2, Source::Output)? > 7050000000 {"));
+        assert!(!main.contains("load_cell_capacity(2, Source::Output)? != "));
+        assert!(main.contains("if !(input_capacity >= output_capacity) {"));
+        assert!(main.contains("!= &[0x01, 0x02][..] {"));
+        assert!(!main.contains("load_cell_lock_hash(0"));
+    }
+}
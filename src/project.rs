@@ -1,8 +1,12 @@
+use crate::docker::{BUILDER_IMAGE_NAME, IMAGE_NAME, INDEXER_IMAGE_NAME};
 use crate::{TrampolineResource, TrampolineResourceType, TEMPLATES};
 use anyhow::Result;
 use ckb_app_config::CKBAppConfig;
+use ckb_jsonrpc_types::DepType;
+use ckb_types::{packed, prelude::*, H256};
 use serde::{Deserialize, Serialize};
 
+use std::collections::BTreeMap;
 use std::convert::From;
 
 use std::fmt::Formatter;
@@ -15,9 +19,14 @@ use thiserror::Error;
 use toml;
 
 pub const TRAMPOLINE_ROOT_CONFIG: &str = "trampoline.toml";
+pub const TRAMPOLINE_ENV_CONFIG: &str = "trampoline-env.toml";
 pub const TRAMPOLINE_FOLDER: &str = ".trampoline";
 pub const TRAMPOLINE_SCHEMAS: &str = "schemas";
 pub const TRAMPOLINE_ROOT_DB_DIR: &str = "cache";
+pub const DEFAULT_ENVIRONMENT: &str = "dev";
+
+// Templates rendered by other commands rather than into new projects
+const RESOURCE_TEMPLATES: [&str; 3] = ["pattern/", "script/", "tests/"];
 
 #[derive(Debug, Error)]
 pub enum TrampolineProjectError {
@@ -34,6 +43,18 @@ pub enum TrampolineProjectError {
     #[error(transparent)]
     SerializeToml(#[from] toml::ser::Error),
 
+    #[error(transparent)]
+    EditToml(#[from] toml_edit::TomlError),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("No ckb.toml found at {0}. Run `trampoline network init` first")]
+    CkbConfigNotFound(String),
+
+    #[error("Unknown environment {name}. Declare it under [environments.{name}] in trampoline.toml (available: {available})")]
+    UnknownEnvironment { name: String, available: String },
+
     #[error("No Trampoline project found within directory {0}")]
     ProjectNotFound(String),
 
@@ -50,6 +71,9 @@ pub struct VirtualEnv {
     pub host_port: usize,
     pub local_binding: PathBuf,
     pub container_mount: String,
+    // Only read from `[chain.spec]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spec: Option<ChainSpecConfig>,
 }
 
 impl std::fmt::Display for VirtualEnv {
@@ -63,17 +87,222 @@ impl std::fmt::Display for VirtualEnv {
         )
     }
 }
+/// Overrides applied to ckb's bundled dev chain spec by `trampoline network init`.
+/// Unset values keep the bundled defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ChainSpecConfig {
+    // Cells created in the genesis block in addition to the bundled ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub issued_cells: Vec<IssuedCellConfig>,
+    pub epoch_duration_target: Option<u64>,
+    pub genesis_epoch_length: Option<u64>,
+    pub permanent_difficulty_in_dummy: Option<bool>,
+    pub cellbase_maturity: Option<u64>,
+    // Also issue a genesis cell to every account of the `dev` environment
+    #[serde(default)]
+    pub fund_accounts: bool,
+    // Shannons issued to each account when `fund_accounts` is set
+    pub fund_capacity: Option<u64>,
+}
+
+impl ChainSpecConfig {
+    pub const DEFAULT_FUND_CAPACITY: u64 = 1_000_000 * 100_000_000;
+
+    pub fn fund_capacity(&self) -> u64 {
+        self.fund_capacity.unwrap_or(Self::DEFAULT_FUND_CAPACITY)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IssuedCellConfig {
+    // In shannons
+    pub capacity: u64,
+    pub lock: LockConfig,
+}
+
+/// A lock script, defaulting to secp256k1-blake160 so only `args` is required.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockConfig {
+    #[serde(default = "LockConfig::default_code_hash")]
+    pub code_hash: String,
+    #[serde(default = "LockConfig::default_hash_type")]
+    pub hash_type: String,
+    pub args: String,
+}
+
+impl LockConfig {
+    pub const SECP256K1_BLAKE160_CODE_HASH: &'static str =
+        "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8";
+
+    fn default_code_hash() -> String {
+        Self::SECP256K1_BLAKE160_CODE_HASH.to_string()
+    }
+
+    fn default_hash_type() -> String {
+        "type".to_string()
+    }
+}
+
+/// Fields of a [`VirtualEnv`] changed by `trampoline network config`.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualEnvUpdate {
+    pub host: Option<String>,
+    pub host_port: Option<usize>,
+    pub local_binding: Option<PathBuf>,
+}
+
+impl VirtualEnvUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.host.is_none() && self.host_port.is_none() && self.local_binding.is_none()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RuntimeConfig {
+    // `auto`, `docker`, `podman`, `native`, or the name of any docker-compatible binary
+    pub engine: Option<String>,
+    pub image: Option<String>,
+    pub indexer_image: Option<String>,
+    // Image `trampoline script build` compiles contracts in
+    pub builder_image: Option<String>,
+    // Binaries used by the `native` engine
+    pub ckb_bin: Option<String>,
+    pub indexer_bin: Option<String>,
+    // Local trampoline sources the tests crate depends on instead of the release
+    pub trampoline_path: Option<String>,
+}
+
+impl RuntimeConfig {
+    pub fn image(&self) -> &str {
+        self.image.as_deref().unwrap_or(IMAGE_NAME)
+    }
+
+    pub fn indexer_image(&self) -> &str {
+        self.indexer_image.as_deref().unwrap_or(INDEXER_IMAGE_NAME)
+    }
+
+    pub fn builder_image(&self) -> &str {
+        self.builder_image.as_deref().unwrap_or(BUILDER_IMAGE_NAME)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct TrampolineEnv {
     pub chain: VirtualEnv,
     pub miner: VirtualEnv,
     pub indexer: VirtualEnv,
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    // Written by `trampoline script deploy`, keyed by script name
+    #[serde(default)]
+    pub deployed: BTreeMap<String, DeployedScript>,
+}
+
+/// A script binary living in a cell of the local chain, as recorded under `[deployed.<name>]`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeployedScript {
+    pub tx_hash: H256,
+    pub index: u32,
+    // Blake2b hash of the binary, the code hash for `hash_type = "data"`
+    pub data_hash: H256,
+    // Hash of the Type ID type script, the code hash for `hash_type = "type"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_hash: Option<H256>,
+    pub dep_type: DepType,
+    // Cells replaced by `trampoline script upgrade`, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<DeployedVersion>,
+}
+
+/// A previous binary of an upgraded script. The cell is spent, but its data stays
+/// available from the transaction that created it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeployedVersion {
+    pub tx_hash: H256,
+    pub index: u32,
+    pub data_hash: H256,
+}
+
+impl DeployedScript {
+    pub fn out_point(&self) -> packed::OutPoint {
+        packed::OutPoint::new(self.tx_hash.pack(), self.index)
+    }
+
+    pub fn version(&self) -> DeployedVersion {
+        DeployedVersion {
+            tx_hash: self.tx_hash.clone(),
+            index: self.index,
+            data_hash: self.data_hash.clone(),
+        }
+    }
+
+    pub fn cell_dep(&self) -> packed::CellDep {
+        packed::CellDep::new_builder()
+            .out_point(self.out_point())
+            .dep_type(ckb_types::core::DepType::from(self.dep_type.clone()).into())
+            .build()
+    }
+}
+
+/// Network an environment's addresses and keys belong to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainType {
+    #[default]
+    Dev,
+    Testnet,
+    Mainnet,
+}
+
+impl ChainType {
+    pub fn is_mainnet(&self) -> bool {
+        matches!(self, Self::Mainnet)
+    }
+}
+
+impl std::fmt::Display for ChainType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dev => write!(f, "dev"),
+            Self::Testnet => write!(f, "testnet"),
+            Self::Mainnet => write!(f, "mainnet"),
+        }
+    }
+}
+
+/// An `[environments.<name>]` entry of `trampoline.toml`. Environments
+/// without RPC endpoints use the local network from `trampoline-env.toml`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EnvironmentConfig {
+    #[serde(default)]
+    pub chain: ChainType,
+    pub ckb_rpc: Option<String>,
+    pub indexer_rpc: Option<String>,
+}
+
+/// An environment with its endpoints resolved.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    pub name: String,
+    pub chain: ChainType,
+    pub ckb_rpc: String,
+    pub indexer_rpc: String,
+    // Whether this environment targets the network managed by `trampoline network`
+    pub local: bool,
+}
+
+impl std::fmt::Display for Environment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}, {})", self.name, self.chain, self.ckb_rpc)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct TrampolineConfig {
     pub name: String,
     pub env: Option<TrampolineEnv>,
+    #[serde(default)]
+    pub environments: BTreeMap<String, EnvironmentConfig>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -127,9 +356,14 @@ impl TrampolineResource for TrampolineProject {
             match root_trampoline_path {
                 Some(mut path) => {
                     let raw_conf = fs::read_to_string(&path).map_err(TrampolineProjectError::Io)?;
-                    let config = toml::from_str::<TrampolineConfig>(&raw_conf)
+                    let mut config = toml::from_str::<TrampolineConfig>(&raw_conf)
                         .map_err(TrampolineProjectError::DeserializeToml)?;
                     path.pop();
+                    let env_path = path.join(TRAMPOLINE_ENV_CONFIG);
+                    if env_path.exists() {
+                        let raw_env = fs::read_to_string(&env_path)?;
+                        config.env = Some(toml::from_str::<TrampolineEnv>(&raw_env)?);
+                    }
                     Ok(TrampolineProject {
                         config,
                         root_dir: path,
@@ -184,6 +418,12 @@ impl TrampolineResource for TrampolineProject {
         context.insert("PROJECT_NAME", &name);
 
         for path in TEMPLATES.get_template_names() {
+            if RESOURCE_TEMPLATES
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+            {
+                continue;
+            }
             println!("PATH: {}", path);
             while !&project_dir.ends_with(&name) {
                 project_dir.pop();
@@ -235,16 +475,169 @@ impl TrampolineProject {
         Ok(())
     }
 
+    /// Sets `listen_address` under `[rpc]` in the node's ckb.toml, keeping the rest
+    /// of the file as it is.
+    pub fn set_rpc_listen_address(&self, address: &str) -> ProjectResult<()> {
+        let ckb_toml_path = self.path_to_ckb_config()?;
+        let mut doc = fs::read_to_string(&ckb_toml_path)?.parse::<toml_edit::Document>()?;
+        doc["rpc"]["listen_address"] = toml_edit::value(address);
+        fs::write(ckb_toml_path, doc.to_string())?;
+        Ok(())
+    }
+
+    /// Resolves an environment declared in `trampoline.toml`. `dev` always
+    /// exists and defaults to the local network.
+    pub fn environment(&self, name: &str) -> ProjectResult<Environment> {
+        let config = match self.config.environments.get(name) {
+            Some(config) => config.clone(),
+            None if name == DEFAULT_ENVIRONMENT => EnvironmentConfig::default(),
+            None => {
+                let mut available = vec![DEFAULT_ENVIRONMENT.to_string()];
+                available.extend(
+                    self.config
+                        .environments
+                        .keys()
+                        .filter(|key| key.as_str() != DEFAULT_ENVIRONMENT)
+                        .cloned(),
+                );
+                return Err(TrampolineProjectError::UnknownEnvironment {
+                    name: name.to_string(),
+                    available: available.join(", "),
+                });
+            }
+        };
+
+        let env = self.config.env.as_ref();
+        let local_url = |service: Option<&VirtualEnv>| {
+            service.map(|service| format!("{}:{}", service.host, service.host_port))
+        };
+        let missing = |field: &str| {
+            TrampolineProjectError::InvalidConfig(format!(
+                "environment {} has no {} and no local network is configured",
+                name, field
+            ))
+        };
+        let local = config.ckb_rpc.is_none();
+        let ckb_rpc = config
+            .ckb_rpc
+            .or_else(|| local_url(env.map(|env| &env.chain)))
+            .ok_or_else(|| missing("ckb_rpc"))?;
+        let indexer_rpc = config
+            .indexer_rpc
+            .or_else(|| local_url(env.map(|env| &env.indexer)))
+            .ok_or_else(|| missing("indexer_rpc"))?;
+        Ok(Environment {
+            name: name.to_string(),
+            chain: config.chain,
+            ckb_rpc,
+            indexer_rpc,
+            local,
+        })
+    }
+
+    pub fn env_config_path(&self) -> PathBuf {
+        self.root_dir.join(TRAMPOLINE_ENV_CONFIG)
+    }
+
+    // Edits `[section]` of trampoline-env.toml in place so comments and the
+    // layout of everything else survive, then reloads the environment.
+    pub fn update_virtual_env(
+        &mut self,
+        section: &str,
+        update: &VirtualEnvUpdate,
+    ) -> ProjectResult<()> {
+        let env_path = self.env_config_path();
+        let mut doc = fs::read_to_string(&env_path)?.parse::<toml_edit::Document>()?;
+        let current = toml::from_str::<TrampolineEnv>(&doc.to_string())?;
+        let current = match section {
+            "chain" => current.chain,
+            "miner" => current.miner,
+            "indexer" => current.indexer,
+            _ => {
+                return Err(TrampolineProjectError::InvalidConfig(format!(
+                    "unknown service {}",
+                    section
+                )))
+            }
+        };
+
+        if let Some(host) = &update.host {
+            doc[section]["host"] = toml_edit::value(host.as_str());
+        }
+        if let Some(port) = update.host_port {
+            if port != current.host_port && !port_is_free(port) {
+                return Err(TrampolineProjectError::InvalidConfig(format!(
+                    "port {} is already in use",
+                    port
+                )));
+            }
+            doc[section]["host_port"] = toml_edit::value(port as i64);
+        }
+        if let Some(local_binding) = &update.local_binding {
+            if !self.root_dir.join(local_binding).is_dir() {
+                return Err(TrampolineProjectError::InvalidConfig(format!(
+                    "{} is not a directory",
+                    local_binding.display()
+                )));
+            }
+            doc[section]["local_binding"] =
+                toml_edit::value(local_binding.to_string_lossy().as_ref());
+        }
+
+        let raw_env = doc.to_string();
+        let env = toml::from_str::<TrampolineEnv>(&raw_env)?;
+        fs::write(&env_path, raw_env)?;
+        self.config.env = Some(env);
+        Ok(())
+    }
+
+    /// Where `name` was deployed on the local chain, if it was.
+    pub fn deployed(&self, name: &str) -> Option<&DeployedScript> {
+        self.config.env.as_ref()?.deployed.get(name)
+    }
+
+    // Replaces `[deployed.<name>]` in trampoline-env.toml, keeping the rest of the file as is
+    pub fn record_deployment(
+        &mut self,
+        name: &str,
+        deployed: &DeployedScript,
+    ) -> ProjectResult<()> {
+        let env_path = self.env_config_path();
+        let mut doc = fs::read_to_string(&env_path)?.parse::<toml_edit::Document>()?;
+        let record = toml::to_string(deployed)?.parse::<toml_edit::Document>()?;
+        doc["deployed"][name] = toml_edit::Item::Table(record.as_table().clone());
+
+        let raw_env = doc.to_string();
+        let env = toml::from_str::<TrampolineEnv>(&raw_env)?;
+        fs::write(&env_path, raw_env)?;
+        self.config.env = Some(env);
+        Ok(())
+    }
+
     pub fn path_to_ckb_config(&self) -> ProjectResult<PathBuf> {
-        let path_to_conf = fs::read_to_string(self.root_dir.join("trampoline-env.toml"))?;
+        let path_to_conf = fs::read_to_string(self.env_config_path())?;
 
         let env = toml::from_str::<TrampolineEnv>(path_to_conf.as_str())?;
 
-        let ckb_toml_path = env.chain.local_binding.join("ckb.toml").canonicalize()?;
+        let ckb_toml_path = self
+            .root_dir
+            .join(&env.chain.local_binding)
+            .join("ckb.toml");
+        if !ckb_toml_path.exists() {
+            return Err(TrampolineProjectError::CkbConfigNotFound(
+                ckb_toml_path.display().to_string(),
+            ));
+        }
         Ok(ckb_toml_path)
     }
 }
 
+fn port_is_free(port: usize) -> bool {
+    u16::try_from(port)
+        .map(|port| std::net::TcpListener::bind(("0.0.0.0", port)).is_ok())
+        .unwrap_or(false)
+}
+
 fn find_ancestor(curr_path: &mut PathBuf, target: &str) -> Option<PathBuf> {
     let target_path = curr_path.join(target);
     if target_path.exists() {
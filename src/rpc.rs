@@ -0,0 +1,359 @@
+use crate::project::{TrampolineProject, VirtualEnv};
+
+use ckb_jsonrpc_types::{
+    BlockNumber, BlockView, CellWithStatus, Consensus, HeaderView, JsonBytes, OutPoint,
+    OutputsValidator, RawTxPool, Script, Transaction, TransactionWithStatus, TxPoolInfo,
+};
+use ckb_types::H256;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use thiserror::Error;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum RpcError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("RPC error {code} from {url}: {message}")]
+    Rpc {
+        url: String,
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+    #[error("Missing network configuration in trampoline-env.toml")]
+    MissingEnv,
+}
+
+pub type RpcResult<T> = std::result::Result<T, RpcError>;
+
+#[derive(Deserialize)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    result: Value,
+    error: Option<ErrorObject>,
+}
+
+/// JSON-RPC client of a ckb node.
+#[derive(Debug)]
+pub struct RpcClient {
+    url: String,
+    client: reqwest::Client,
+    id: AtomicU64,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Self {
+        Self::with_timeout(url, DEFAULT_TIMEOUT)
+    }
+
+    pub fn with_timeout(url: &str, timeout: Duration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("static client configuration");
+        RpcClient {
+            url: url.to_string(),
+            client,
+            id: AtomicU64::new(0),
+        }
+    }
+
+    pub fn from_env(env: &VirtualEnv) -> Self {
+        Self::new(&format!("{}:{}", env.host, env.host_port))
+    }
+
+    /// Client for the node described by `[chain]` in `trampoline-env.toml`.
+    pub fn for_project(project: &TrampolineProject) -> RpcResult<Self> {
+        let env = project.config.env.as_ref().ok_or(RpcError::MissingEnv)?;
+        Ok(Self::from_env(&env.chain))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Calls any method, for those without a typed wrapper.
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> RpcResult<T> {
+        let request = json!({
+            "id": self.id.fetch_add(1, Ordering::Relaxed),
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        });
+        let response: Response = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Some(error) = response.error {
+            return Err(RpcError::Rpc {
+                url: self.url.clone(),
+                code: error.code,
+                message: error.message,
+                data: error.data,
+            });
+        }
+        Ok(serde_json::from_value(response.result)?)
+    }
+
+    pub async fn get_tip_header(&self) -> RpcResult<HeaderView> {
+        self.call("get_tip_header", json!([])).await
+    }
+
+    pub async fn get_tip_block_number(&self) -> RpcResult<BlockNumber> {
+        self.call("get_tip_block_number", json!([])).await
+    }
+
+    pub async fn get_block_by_number(&self, number: u64) -> RpcResult<Option<BlockView>> {
+        self.call("get_block_by_number", json!([BlockNumber::from(number)]))
+            .await
+    }
+
+    pub async fn get_header_by_number(&self, number: u64) -> RpcResult<Option<HeaderView>> {
+        self.call("get_header_by_number", json!([BlockNumber::from(number)]))
+            .await
+    }
+
+    pub async fn get_consensus(&self) -> RpcResult<Consensus> {
+        self.call("get_consensus", json!([])).await
+    }
+
+    pub async fn get_transaction(&self, hash: &H256) -> RpcResult<Option<TransactionWithStatus>> {
+        self.call("get_transaction", json!([hash])).await
+    }
+
+    pub async fn get_live_cell(
+        &self,
+        out_point: &OutPoint,
+        with_data: bool,
+    ) -> RpcResult<CellWithStatus> {
+        self.call("get_live_cell", json!([out_point, with_data]))
+            .await
+    }
+
+    pub async fn send_transaction(
+        &self,
+        tx: &Transaction,
+        outputs_validator: Option<OutputsValidator>,
+    ) -> RpcResult<H256> {
+        self.call("send_transaction", json!([tx, outputs_validator]))
+            .await
+    }
+
+    pub async fn tx_pool_info(&self) -> RpcResult<TxPoolInfo> {
+        self.call("tx_pool_info", json!([])).await
+    }
+
+    pub async fn get_raw_tx_pool(&self, verbose: bool) -> RpcResult<RawTxPool> {
+        self.call("get_raw_tx_pool", json!([verbose])).await
+    }
+
+    pub async fn clear_tx_pool(&self) -> RpcResult<()> {
+        self.call("clear_tx_pool", json!([])).await
+    }
+
+    /// Mines a block immediately. Only available on dev chains with the
+    /// `IntegrationTest` module listed under `[rpc] modules` in `ckb.toml`.
+    pub async fn generate_block(
+        &self,
+        block_assembler_script: Option<Script>,
+        block_assembler_message: Option<JsonBytes>,
+    ) -> RpcResult<H256> {
+        self.call(
+            "generate_block",
+            json!([block_assembler_script, block_assembler_message]),
+        )
+        .await
+    }
+}
+
+/// Blocking facade over [`RpcClient`] for synchronous code such as the CLI.
+/// Must not be used from within an async runtime.
+#[derive(Debug)]
+pub struct BlockingRpcClient {
+    client: RpcClient,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl BlockingRpcClient {
+    pub fn new(client: RpcClient) -> RpcResult<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(BlockingRpcClient { client, runtime })
+    }
+
+    pub fn for_project(project: &TrampolineProject) -> RpcResult<Self> {
+        Self::new(RpcClient::for_project(project)?)
+    }
+
+    pub fn client(&self) -> &RpcClient {
+        &self.client
+    }
+
+    pub fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> RpcResult<T> {
+        self.runtime.block_on(self.client.call(method, params))
+    }
+
+    pub fn get_tip_header(&self) -> RpcResult<HeaderView> {
+        self.runtime.block_on(self.client.get_tip_header())
+    }
+
+    pub fn get_tip_block_number(&self) -> RpcResult<BlockNumber> {
+        self.runtime.block_on(self.client.get_tip_block_number())
+    }
+
+    pub fn get_block_by_number(&self, number: u64) -> RpcResult<Option<BlockView>> {
+        self.runtime
+            .block_on(self.client.get_block_by_number(number))
+    }
+
+    pub fn get_header_by_number(&self, number: u64) -> RpcResult<Option<HeaderView>> {
+        self.runtime
+            .block_on(self.client.get_header_by_number(number))
+    }
+
+    pub fn get_consensus(&self) -> RpcResult<Consensus> {
+        self.runtime.block_on(self.client.get_consensus())
+    }
+
+    pub fn get_transaction(&self, hash: &H256) -> RpcResult<Option<TransactionWithStatus>> {
+        self.runtime.block_on(self.client.get_transaction(hash))
+    }
+
+    pub fn get_live_cell(
+        &self,
+        out_point: &OutPoint,
+        with_data: bool,
+    ) -> RpcResult<CellWithStatus> {
+        self.runtime
+            .block_on(self.client.get_live_cell(out_point, with_data))
+    }
+
+    pub fn send_transaction(
+        &self,
+        tx: &Transaction,
+        outputs_validator: Option<OutputsValidator>,
+    ) -> RpcResult<H256> {
+        self.runtime
+            .block_on(self.client.send_transaction(tx, outputs_validator))
+    }
+
+    pub fn tx_pool_info(&self) -> RpcResult<TxPoolInfo> {
+        self.runtime.block_on(self.client.tx_pool_info())
+    }
+
+    pub fn get_raw_tx_pool(&self, verbose: bool) -> RpcResult<RawTxPool> {
+        self.runtime.block_on(self.client.get_raw_tx_pool(verbose))
+    }
+
+    pub fn clear_tx_pool(&self) -> RpcResult<()> {
+        self.runtime.block_on(self.client.clear_tx_pool())
+    }
+
+    pub fn generate_block(
+        &self,
+        block_assembler_script: Option<Script>,
+        block_assembler_message: Option<JsonBytes>,
+    ) -> RpcResult<H256> {
+        self.runtime.block_on(
+            self.client
+                .generate_block(block_assembler_script, block_assembler_message),
+        )
+    }
+}
+
+/// Local HTTP server answering JSON-RPC requests with canned responses.
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // Answers one request per body, in order, and returns the request bodies it received
+    pub fn serve(bodies: Vec<String>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = vec![];
+            for body in bodies {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut request = vec![0; content_length];
+                reader.read_exact(&mut request).unwrap();
+                // Closing the connection makes the client reconnect for the next body
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                requests.push(String::from_utf8(request).unwrap());
+            }
+            requests
+        });
+        (url, handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_result() {
+        let (url, server) = mock::serve(vec![
+            r#"{"jsonrpc":"2.0","id":0,"result":"0x400"}"#.to_string()
+        ]);
+        let client = BlockingRpcClient::new(RpcClient::new(&url)).unwrap();
+        assert_eq!(client.get_tip_block_number().unwrap().value(), 1024);
+        let request: Value = serde_json::from_str(&server.join().unwrap()[0]).unwrap();
+        assert_eq!(request["method"], "get_tip_block_number");
+    }
+
+    #[test]
+    fn test_rpc_error() {
+        let (url, _server) = mock::serve(vec![
+            r#"{"jsonrpc":"2.0","id":0,"error":{"code":-301,"message":"TransactionFailedToResolve"}}"#.to_string(),
+        ]);
+        let client = BlockingRpcClient::new(RpcClient::new(&url)).unwrap();
+        match client.clear_tx_pool() {
+            Err(RpcError::Rpc { code, message, .. }) => {
+                assert_eq!(code, -301);
+                assert_eq!(message, "TransactionFailedToResolve");
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -1,3 +1,7 @@
+mod encode;
+
+pub use encode::encode;
+
 use crate::project::TrampolineProject;
 use crate::{TrampolineResource, TrampolineResourceType};
 use anyhow::Result;
@@ -16,6 +20,10 @@ pub enum SchemaError {
     Io(#[from] std::io::Error),
     #[error("Error compiling molecule schema file:\n {0}")]
     Molecule(String),
+    #[error("No type {0} in schema {1}")]
+    UnknownType(String, String),
+    #[error("Cannot encode {0}")]
+    Encode(String),
 }
 
 #[derive(Debug, Clone, Default)]
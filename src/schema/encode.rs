@@ -0,0 +1,237 @@
+use super::{SchemaError, SchemaResult};
+
+use molecule_codegen::ast::{Ast, HasName, TopDecl};
+use molecule_codegen::Parser;
+use std::panic;
+use std::path::Path;
+use toml::Value;
+
+// Byte arrays of these sizes also accept an integer, encoded little endian
+const INTEGER_SIZES: [usize; 5] = [1, 2, 4, 8, 16];
+
+/// Serializes `value` as the molecule type `type_name` declared in a `.mol` file.
+///
+/// Structs and tables take a TOML table keyed by field name, arrays and vectors a TOML
+/// array, and unions a table with a single key naming the variant. Byte arrays and
+/// vectors also take a `0x` hex string, byte vectors a plain string as UTF-8, and byte
+/// arrays of 1, 2, 4, 8 or 16 bytes an integer. Missing table fields of option types
+/// are encoded as `None`.
+pub fn encode(schema: &Path, type_name: &str, value: &Value) -> SchemaResult<Vec<u8>> {
+    let ast = parse(schema)?;
+    let decl = ast
+        .decls()
+        .iter()
+        .find(|decl| decl.name() == type_name)
+        .ok_or_else(|| {
+            SchemaError::UnknownType(type_name.to_string(), schema.display().to_string())
+        })?;
+    encode_decl(decl, value, type_name)
+}
+
+fn parse(schema: &Path) -> SchemaResult<Ast> {
+    if !schema.exists() {
+        return Err(SchemaError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("schema {} not found", schema.display()),
+        )));
+    }
+    // The molecule parser panics on invalid schemas
+    panic::catch_unwind(|| Parser::parse(&schema))
+        .map_err(|_| SchemaError::Molecule(format!("cannot parse {}", schema.display())))
+}
+
+fn encode_decl(decl: &TopDecl, value: &Value, path: &str) -> SchemaResult<Vec<u8>> {
+    match decl {
+        TopDecl::Primitive(_) => bytes(value, Some(1), path),
+        TopDecl::Array(array) if array.item().typ().is_byte() => {
+            bytes(value, Some(array.item_count()), path)
+        }
+        TopDecl::Array(array) => {
+            let items = items(value, path)?;
+            if items.len() != array.item_count() {
+                return Err(invalid(
+                    path,
+                    &format!("expected {} items", array.item_count()),
+                ));
+            }
+            Ok(encode_items(array.item().typ(), items, path)?.concat())
+        }
+        TopDecl::Struct(decl) => {
+            let table = table(value, path)?;
+            let mut encoded = vec![];
+            for field in decl.fields() {
+                let field_path = format!("{}.{}", path, field.name());
+                let field_value = table
+                    .get(field.name())
+                    .ok_or_else(|| invalid(&field_path, "missing field"))?;
+                encoded.extend(encode_decl(field.typ(), field_value, &field_path)?);
+            }
+            Ok(encoded)
+        }
+        TopDecl::FixVec(vector) => {
+            let encoded = if vector.item().typ().is_byte() {
+                bytes(value, None, path)?
+            } else {
+                let items = items(value, path)?;
+                encode_items(vector.item().typ(), items, path)?.concat()
+            };
+            let count = encoded.len() / vector.item_size();
+            let mut fixvec = (count as u32).to_le_bytes().to_vec();
+            fixvec.extend(encoded);
+            Ok(fixvec)
+        }
+        TopDecl::DynVec(vector) => {
+            let items = items(value, path)?;
+            Ok(dynamic(encode_items(vector.item().typ(), items, path)?))
+        }
+        TopDecl::Table(decl) => {
+            let table = table(value, path)?;
+            let mut fields = vec![];
+            for field in decl.fields() {
+                let field_path = format!("{}.{}", path, field.name());
+                let encoded = match table.get(field.name()) {
+                    Some(field_value) => encode_decl(field.typ(), field_value, &field_path)?,
+                    None if matches!(field.typ().as_ref(), TopDecl::Option_(_)) => vec![],
+                    None => return Err(invalid(&field_path, "missing field")),
+                };
+                fields.push(encoded);
+            }
+            Ok(dynamic(fields))
+        }
+        TopDecl::Option_(option) => encode_decl(option.item().typ(), value, path),
+        TopDecl::Union(union) => {
+            let table = table(value, path)?;
+            let (variant, variant_value) = match table.iter().next() {
+                Some(entry) if table.len() == 1 => entry,
+                _ => return Err(invalid(path, "expected a table with a single variant")),
+            };
+            let id = union
+                .items()
+                .iter()
+                .position(|item| item.typ().name() == variant)
+                .ok_or_else(|| invalid(path, &format!("unknown variant {}", variant)))?;
+            let variant_path = format!("{}.{}", path, variant);
+            let mut encoded = (id as u32).to_le_bytes().to_vec();
+            encoded.extend(encode_decl(
+                union.items()[id].typ(),
+                variant_value,
+                &variant_path,
+            )?);
+            Ok(encoded)
+        }
+    }
+}
+
+fn encode_items(item: &TopDecl, items: &[Value], path: &str) -> SchemaResult<Vec<Vec<u8>>> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, value)| encode_decl(item, value, &format!("{}[{}]", path, index)))
+        .collect()
+}
+
+// Header of the total size and each item's offset, followed by the items
+fn dynamic(items: Vec<Vec<u8>>) -> Vec<u8> {
+    let header_size = 4 * (1 + items.len());
+    let total_size = header_size + items.iter().map(Vec::len).sum::<usize>();
+    let mut encoded = (total_size as u32).to_le_bytes().to_vec();
+    let mut offset = header_size;
+    for item in &items {
+        encoded.extend((offset as u32).to_le_bytes());
+        offset += item.len();
+    }
+    encoded.extend(items.concat());
+    encoded
+}
+
+fn bytes(value: &Value, size: Option<usize>, path: &str) -> SchemaResult<Vec<u8>> {
+    let encoded = match value {
+        Value::String(hex) if hex.starts_with("0x") => {
+            hex::decode(&hex[2..]).map_err(|e| invalid(path, &format!("invalid hex: {}", e)))?
+        }
+        Value::String(text) if size.is_none() => text.as_bytes().to_vec(),
+        Value::Integer(integer) => match size {
+            Some(size) if INTEGER_SIZES.contains(&size) => {
+                let integer = u128::try_from(*integer)
+                    .map_err(|_| invalid(path, "negative integers are not supported"))?;
+                let encoded = integer.to_le_bytes();
+                if encoded[size..].iter().any(|byte| *byte != 0) {
+                    return Err(invalid(path, &format!("does not fit in {} bytes", size)));
+                }
+                encoded[..size].to_vec()
+            }
+            _ => return Err(invalid(path, "expected a hex string")),
+        },
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Integer(byte) => u8::try_from(*byte).ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid(path, "expected an array of bytes"))?,
+        _ => return Err(invalid(path, "expected bytes")),
+    };
+    match size {
+        Some(size) if encoded.len() != size => {
+            Err(invalid(path, &format!("expected {} bytes", size)))
+        }
+        _ => Ok(encoded),
+    }
+}
+
+fn items<'a>(value: &'a Value, path: &str) -> SchemaResult<&'a [Value]> {
+    value
+        .as_array()
+        .map(Vec::as_slice)
+        .ok_or_else(|| invalid(path, "expected an array"))
+}
+
+fn table<'a>(value: &'a Value, path: &str) -> SchemaResult<&'a toml::value::Table> {
+    value
+        .as_table()
+        .ok_or_else(|| invalid(path, "expected a table"))
+}
+
+fn invalid(path: &str, reason: &str) -> SchemaError {
+    SchemaError::Encode(format!("{}: {}", path, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+array Uint32 [byte; 4];
+vector Bytes <byte>;
+option BytesOpt (Bytes);
+struct Point { x: Uint32, y: Uint32, }
+table Token { symbol: Bytes, supply: Uint32, origin: Point, memo: BytesOpt, }
+"#;
+
+    #[test]
+    fn test_encode_table() {
+        let path = std::env::temp_dir().join(format!("trampoline-{}.mol", std::process::id()));
+        std::fs::write(&path, SCHEMA).unwrap();
+        let value = r#"
+symbol = "TKN"
+supply = 1000
+origin = { x = 1, y = "0x02000000" }
+"#
+        .parse::<Value>()
+        .unwrap();
+        let encoded = encode(&path, "Token", &value).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut expected = vec![];
+        // Total size and the offsets of the four fields, the last one empty
+        for word in [39u32, 20, 27, 31, 39] {
+            expected.extend(word.to_le_bytes());
+        }
+        expected.extend(3u32.to_le_bytes());
+        expected.extend(b"TKN");
+        expected.extend(1000u32.to_le_bytes());
+        expected.extend([1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(encoded, expected);
+    }
+}
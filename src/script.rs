@@ -0,0 +1,397 @@
+mod deploy;
+
+pub use deploy::{Deployer, DEFAULT_COMMIT_TIMEOUT, TYPE_ID_CODE_HASH};
+
+use crate::docker::{backend_from_config, DockerContainer, DockerError, DockerImage, Volume};
+use crate::indexer::IndexerError;
+use crate::network::runtime_config;
+use crate::project::{
+    TrampolineProject, TrampolineProjectError, TRAMPOLINE_FOLDER, TRAMPOLINE_ROOT_DB_DIR,
+};
+use crate::rpc::RpcError;
+use crate::testing::PROFILE_ENV;
+use crate::transaction::TransactionError;
+use crate::TEMPLATES;
+
+use ckb_hash::blake2b_256;
+use ckb_types::H256;
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tera::Context as TeraContext;
+use thiserror::Error;
+
+pub const SCRIPTS_FOLDER: &str = "scripts";
+pub const BUILD_FOLDER: &str = "build";
+pub const SCRIPT_TARGET: &str = "riscv64imac-unknown-none-elf";
+// Written next to the binaries, mapping each script name to its code hash
+pub const CODE_HASHES_FILE: &str = "code_hashes.toml";
+// Host crate with the offline script tests
+pub const TESTS_FOLDER: &str = "tests";
+
+// Rendered into a new script crate, relative to its root
+const CRATE_TEMPLATES: [&str; 3] = ["Cargo.toml", "src/main.rs", "src/error.rs"];
+const TESTS_CRATE_TEMPLATES: [&str; 2] = ["Cargo.toml", "src/lib.rs"];
+// The tests crate depends on the trampoline release matching this binary, which ships
+// the `testing` module the tests use
+const TRAMPOLINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+// Where the builder container sees the project's scripts and cargo cache
+const CONTAINER_SCRIPTS: &str = "/code";
+const CONTAINER_CARGO_REGISTRY: &str = "/root/.cargo/registry";
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Tera(#[from] tera::Error),
+    #[error(transparent)]
+    Docker(#[from] DockerError),
+    #[error(transparent)]
+    DeserializeToml(#[from] toml::de::Error),
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    #[error(transparent)]
+    Indexer(#[from] IndexerError),
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+    #[error(transparent)]
+    Project(#[from] TrampolineProjectError),
+    #[error("Script {0} already exists")]
+    AlreadyExists(String),
+    #[error("No script named {0} in {}", SCRIPTS_FOLDER)]
+    NotFound(String),
+    #[error("Invalid script name {0}: use letters, digits, `-` and `_`")]
+    InvalidName(String),
+    #[error("Building {0} failed")]
+    BuildFailed(String),
+    #[error("Script tests failed")]
+    TestsFailed,
+    #[error("No {1} build of {0}. Run `trampoline script build` first")]
+    NotBuilt(String, BuildProfile),
+    #[error("Scripts are only deployed to the local chain, but environment {0} is not local")]
+    NotLocal(String),
+    #[error("Script {0} is not deployed. Run `trampoline script deploy {0}` first")]
+    NotDeployed(String),
+    #[error("Script {0} was deployed without --type-id and cannot be upgraded in place")]
+    NotUpgradable(String),
+    #[error("The deployed cell of {0} is no longer live")]
+    NotLive(String),
+    #[error("No previous version {0} found")]
+    UnknownVersion(String),
+    #[error("Transaction {0:#x} was rejected: {1}")]
+    Rejected(H256, String),
+    #[error(
+        "Transaction {tx_hash:#x} was not committed within {waited_secs}s. Is a miner running?"
+    )]
+    NotCommitted { tx_hash: H256, waited_secs: u64 },
+}
+
+pub type ScriptResult<T> = std::result::Result<T, ScriptError>;
+
+/// Build profile, which is also the folder under `build/` binaries are written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildProfile {
+    Debug,
+    Release,
+}
+
+impl std::fmt::Display for BuildProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Debug => write!(f, "debug"),
+            Self::Release => write!(f, "release"),
+        }
+    }
+}
+
+/// A contract crate under `scripts/`.
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// A compiled script binary and the blake2b hash cells reference it by.
+#[derive(Debug, Clone)]
+pub struct ScriptBinary {
+    pub name: String,
+    pub path: PathBuf,
+    pub code_hash: H256,
+}
+
+impl std::fmt::Display for ScriptBinary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}\ncode_hash: {:#x}",
+            self.name,
+            self.path.display(),
+            self.code_hash
+        )
+    }
+}
+
+impl Script {
+    pub fn dir(project: &TrampolineProject) -> PathBuf {
+        project.root_dir.join(SCRIPTS_FOLDER)
+    }
+
+    /// Renders a ckb-std contract crate into `scripts/<name>`.
+    pub fn new(project: &TrampolineProject, name: &str) -> ScriptResult<Self> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(ScriptError::InvalidName(name.to_string()));
+        }
+        let path = Self::dir(project).join(name);
+        if path.exists() {
+            return Err(ScriptError::AlreadyExists(name.to_string()));
+        }
+        fs::create_dir_all(path.join("src"))?;
+
+        let mut context = TeraContext::new();
+        context.insert("name", name);
+        for template in CRATE_TEMPLATES {
+            let content = TEMPLATES.render(&format!("script/{}", template), &context)?;
+            fs::write(path.join(template), content)?;
+        }
+        render_tests(project, Some(name))?;
+        Ok(Script {
+            name: name.to_string(),
+            path,
+        })
+    }
+
+    pub fn load(project: &TrampolineProject, name: &str) -> ScriptResult<Self> {
+        let path = Self::dir(project).join(name);
+        if !path.join("Cargo.toml").exists() {
+            return Err(ScriptError::NotFound(name.to_string()));
+        }
+        Ok(Script {
+            name: name.to_string(),
+            path,
+        })
+    }
+
+    /// Every crate under `scripts/`, sorted by name.
+    pub fn list(project: &TrampolineProject) -> ScriptResult<Vec<Self>> {
+        let dir = Self::dir(project);
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut scripts = vec![];
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.join("Cargo.toml").exists() {
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                scripts.push(Script { name, path });
+            }
+        }
+        scripts.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(scripts)
+    }
+
+    /// Path of the compiled binary under `build/<profile>`.
+    pub fn binary_path(&self, project: &TrampolineProject, profile: BuildProfile) -> PathBuf {
+        project
+            .root_dir
+            .join(BUILD_FOLDER)
+            .join(profile.to_string())
+            .join(&self.name)
+    }
+
+    /// The compiled binary in `build/<profile>`.
+    pub fn code(
+        &self,
+        project: &TrampolineProject,
+        profile: BuildProfile,
+    ) -> ScriptResult<Vec<u8>> {
+        fs::read(self.binary_path(project, profile)).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => ScriptError::NotBuilt(self.name.clone(), profile),
+            _ => e.into(),
+        })
+    }
+
+    /// Reads the compiled binary from `build/<profile>` and hashes it.
+    pub fn binary(
+        &self,
+        project: &TrampolineProject,
+        profile: BuildProfile,
+    ) -> ScriptResult<ScriptBinary> {
+        let code = self.code(project, profile)?;
+        Ok(ScriptBinary {
+            name: self.name.clone(),
+            path: self.binary_path(project, profile),
+            code_hash: blake2b_256(&code).into(),
+        })
+    }
+}
+
+/// Cross-compiles `scripts` for CKB-VM and copies the binaries to `build/<profile>`.
+/// Runs inside the pinned builder image, or with the host's cargo when the `native`
+/// engine is selected under `[runtime]`.
+pub fn build(
+    project: &TrampolineProject,
+    scripts: &[Script],
+    profile: BuildProfile,
+) -> ScriptResult<Vec<ScriptBinary>> {
+    let runtime = runtime_config(project);
+    let scripts_dir = Script::dir(project);
+    let target_dir = scripts_dir.join("target");
+    for script in scripts {
+        println!("Building {} ({})", script.name, profile);
+        if runtime.engine.as_deref() == Some("native") {
+            let mut args = vec!["build", "--target", SCRIPT_TARGET];
+            if profile == BuildProfile::Release {
+                args.push("--release");
+            }
+            let status = Command::new("cargo")
+                .args(&args)
+                .env("CARGO_TARGET_DIR", &target_dir)
+                .current_dir(&script.path)
+                .status()?;
+            if !status.success() {
+                return Err(ScriptError::BuildFailed(script.name.clone()));
+            }
+        } else {
+            build_in_container(project, &scripts_dir, script, profile)?;
+        }
+    }
+
+    let out_dir = project
+        .root_dir
+        .join(BUILD_FOLDER)
+        .join(profile.to_string());
+    fs::create_dir_all(&out_dir)?;
+    let hashes_path = out_dir.join(CODE_HASHES_FILE);
+    let mut hashes = match fs::read_to_string(&hashes_path) {
+        Ok(raw) => toml::from_str::<toml::value::Table>(&raw)?,
+        Err(_) => toml::value::Table::new(),
+    };
+    let mut binaries = vec![];
+    for script in scripts {
+        let compiled = target_dir
+            .join(SCRIPT_TARGET)
+            .join(profile.to_string())
+            .join(&script.name);
+        fs::copy(&compiled, script.binary_path(project, profile))?;
+        let binary = script.binary(project, profile)?;
+        hashes.insert(
+            binary.name.clone(),
+            toml::Value::String(format!("{:#x}", binary.code_hash)),
+        );
+        binaries.push(binary);
+    }
+    fs::write(&hashes_path, toml::Value::Table(hashes).to_string())?;
+    Ok(binaries)
+}
+
+/// Runs `cargo test` in the tests crate, which verifies the `profile` builds of the
+/// scripts with `trampoline::testing`. Creates the crate first if the project has none.
+pub fn test(
+    project: &TrampolineProject,
+    profile: BuildProfile,
+    cargo_args: &[String],
+) -> ScriptResult<()> {
+    let tests_dir = render_tests(project, None)?;
+    let status = Command::new("cargo")
+        .arg("test")
+        .args(cargo_args)
+        .env(PROFILE_ENV, profile.to_string())
+        .current_dir(&tests_dir)
+        .status()?;
+    if !status.success() {
+        return Err(ScriptError::TestsFailed);
+    }
+    Ok(())
+}
+
+// Renders the tests crate if missing, and example tests for `script` if it has none
+fn render_tests(project: &TrampolineProject, script: Option<&str>) -> ScriptResult<PathBuf> {
+    let tests_dir = project.root_dir.join(TESTS_FOLDER);
+    let mut context = TeraContext::new();
+    context.insert("project", &project.config.name);
+    context.insert("trampoline_version", TRAMPOLINE_VERSION);
+    if let Some(path) = runtime_config(project).trampoline_path {
+        // Relative to the project root rather than the tests crate
+        context.insert("trampoline_path", &project.root_dir.join(path));
+    }
+    if !tests_dir.join("Cargo.toml").exists() {
+        fs::create_dir_all(tests_dir.join("src"))?;
+        for template in TESTS_CRATE_TEMPLATES {
+            let content = TEMPLATES.render(&format!("tests/{}", template), &context)?;
+            fs::write(tests_dir.join(template), content)?;
+        }
+    }
+    if let Some(name) = script {
+        let path = tests_dir.join("tests").join(format!("{}.rs", name));
+        if !path.exists() {
+            fs::create_dir_all(tests_dir.join("tests"))?;
+            context.insert("name", name);
+            fs::write(path, TEMPLATES.render("tests/tests/script.rs", &context)?)?;
+        }
+    }
+    Ok(tests_dir)
+}
+
+fn build_in_container(
+    project: &TrampolineProject,
+    scripts_dir: &Path,
+    script: &Script,
+    profile: BuildProfile,
+) -> ScriptResult<()> {
+    let runtime = runtime_config(project);
+    let backend = backend_from_config(Some(&runtime))?;
+    // Keeps downloaded crates between builds
+    let registry = project
+        .root_dir
+        .join(TRAMPOLINE_FOLDER)
+        .join(TRAMPOLINE_ROOT_DB_DIR)
+        .join("cargo");
+    fs::create_dir_all(&registry)?;
+
+    let container = DockerContainer {
+        name: format!("{}-script-build", project.config.name),
+        port_bindings: vec![],
+        volumes: vec![
+            Volume {
+                host: scripts_dir,
+                container: Path::new(CONTAINER_SCRIPTS),
+            },
+            Volume {
+                host: &registry,
+                container: Path::new(CONTAINER_CARGO_REGISTRY),
+            },
+        ],
+        env_vars: HashMap::default(),
+        image: DockerImage::from_reference(runtime.builder_image()),
+        network: None,
+    };
+    let release = match profile {
+        BuildProfile::Release => " --release",
+        BuildProfile::Debug => "",
+    };
+    let command = format!(
+        "cd {root}/{name} && CARGO_TARGET_DIR={root}/target cargo build --target {target}{release}",
+        root = CONTAINER_SCRIPTS,
+        name = script.name,
+        target = SCRIPT_TARGET,
+        release = release
+    );
+    if backend.inspect(&container.name)?.is_some() {
+        backend.remove(&container.name)?;
+    }
+    let result = backend.run(
+        &container,
+        &["bash".to_string(), "-c".to_string(), command],
+        false,
+    );
+    backend.remove(&container.name)?;
+    result?;
+    Ok(())
+}
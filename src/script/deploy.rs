@@ -0,0 +1,232 @@
+use super::{BuildProfile, Script, ScriptError, ScriptResult};
+use crate::address::sighash_script;
+use crate::indexer::{BlockingIndexerClient, IndexerClient};
+use crate::project::{DeployedScript, DeployedVersion, Environment, TrampolineProject};
+use crate::rpc::{BlockingRpcClient, RpcClient};
+use crate::transaction::{input_locks, Signer, TransactionBuilder, TransactionError};
+
+use ckb_hash::{blake2b_256, new_blake2b};
+use ckb_jsonrpc_types::{DepType, Status, Transaction};
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType, TransactionView},
+    h256, packed,
+    prelude::*,
+    H256,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Code hash of the built-in Type ID script, `"TYPE_ID"` in ASCII.
+pub const TYPE_ID_CODE_HASH: H256 =
+    h256!("0x00000000000000000000000000000000000000000000000000545950455f4944");
+pub const DEFAULT_COMMIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+const COMMIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Puts compiled scripts on the local chain in cells owned by the signer's lock.
+pub struct Deployer<'a> {
+    project: &'a TrampolineProject,
+    rpc: BlockingRpcClient,
+    indexer: BlockingIndexerClient,
+    signer: Signer,
+    lock: packed::Script,
+    timeout: Duration,
+}
+
+impl<'a> Deployer<'a> {
+    pub fn new(
+        project: &'a TrampolineProject,
+        environment: &Environment,
+        signer: Signer,
+    ) -> ScriptResult<Self> {
+        if !environment.local {
+            return Err(ScriptError::NotLocal(environment.name.clone()));
+        }
+        Ok(Deployer {
+            project,
+            rpc: BlockingRpcClient::new(RpcClient::new(&environment.ckb_rpc))?,
+            indexer: BlockingIndexerClient::new(IndexerClient::new(&environment.indexer_rpc))?,
+            lock: sighash_script(&signer.lock_arg()),
+            signer,
+            timeout: DEFAULT_COMMIT_TIMEOUT,
+        })
+    }
+
+    /// How long to wait for a deployment to be committed.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Creates a cell holding the binary of `script`, optionally under a fresh Type ID,
+    /// and waits until it is committed.
+    pub fn deploy(
+        &self,
+        script: &Script,
+        profile: BuildProfile,
+        type_id: bool,
+    ) -> ScriptResult<DeployedScript> {
+        let code = Bytes::from(script.code(self.project, profile)?);
+        // The Type ID args depend on the first input, so they are filled in after building
+        let type_ = type_id.then(|| type_id_script([0u8; 32]));
+        let output = self.code_cell(self.lock.clone(), type_, &code)?;
+        let tx = TransactionBuilder::new(&self.rpc, &self.indexer)
+            .from_lock(self.lock.clone())
+            .output(output, code.clone())
+            .build()?;
+        let tx = if type_id { with_type_id(tx, 0) } else { tx };
+        self.send(tx, 0, &code)
+    }
+
+    /// Replaces the Type ID cell of `deployed` with one holding `code`. The type hash
+    /// stays the same, and the replaced cell is added to the history.
+    pub fn upgrade(
+        &self,
+        name: &str,
+        deployed: &DeployedScript,
+        code: Bytes,
+    ) -> ScriptResult<DeployedScript> {
+        if deployed.type_hash.is_none() {
+            return Err(ScriptError::NotUpgradable(name.to_string()));
+        }
+        let status = self
+            .rpc
+            .get_live_cell(&deployed.out_point().into(), false)?;
+        let cell = status
+            .cell
+            .filter(|_| status.status == "live")
+            .ok_or_else(|| ScriptError::NotLive(name.to_string()))?;
+        // The old lock and Type ID args carry over, so the type hash stays the same
+        let output = self.code_cell(
+            packed::Script::from(cell.output.lock),
+            cell.output.type_.map(packed::Script::from),
+            &code,
+        )?;
+        let tx = TransactionBuilder::new(&self.rpc, &self.indexer)
+            .input(deployed.out_point())
+            .output(output, code.clone())
+            .from_lock(self.lock.clone())
+            .change_lock(self.lock.clone())
+            .build()?;
+
+        let mut upgraded = self.send(tx, 0, &code)?;
+        upgraded.history = deployed.history.clone();
+        upgraded.history.push(deployed.version());
+        Ok(upgraded)
+    }
+
+    /// Fetches the binary of a previous version from the transaction that created it.
+    pub fn historical_code(&self, version: &DeployedVersion) -> ScriptResult<Bytes> {
+        let code = self
+            .rpc
+            .get_transaction(&version.tx_hash)?
+            .and_then(|tx| tx.transaction)
+            .and_then(|tx| tx.inner.outputs_data.get(version.index as usize).cloned())
+            .map(|data| data.into_bytes())
+            .ok_or_else(|| ScriptError::UnknownVersion(format!("{:#x}", version.data_hash)))?;
+        if H256::from(blake2b_256(&code)) != version.data_hash {
+            return Err(ScriptError::UnknownVersion(format!(
+                "{:#x}",
+                version.data_hash
+            )));
+        }
+        Ok(code)
+    }
+
+    fn code_cell(
+        &self,
+        lock: packed::Script,
+        type_: Option<packed::Script>,
+        code: &Bytes,
+    ) -> ScriptResult<packed::CellOutput> {
+        let output = packed::CellOutput::new_builder()
+            .lock(lock)
+            .type_(type_.pack())
+            .build();
+        let capacity = output
+            .occupied_capacity(Capacity::bytes(code.len()).map_err(TransactionError::from)?)
+            .map_err(TransactionError::from)?;
+        Ok(output.as_builder().capacity(capacity.pack()).build())
+    }
+
+    // Signs every input the signer can unlock, submits, and records where `code` ended up
+    fn send(
+        &self,
+        tx: TransactionView,
+        index: usize,
+        code: &Bytes,
+    ) -> ScriptResult<DeployedScript> {
+        let tx = self.signer.sign(&tx, &input_locks(&self.rpc, &tx)?)?;
+        let tx_hash = self
+            .rpc
+            .send_transaction(&Transaction::from(tx.data()), None)?;
+        self.wait_committed(&tx_hash)?;
+
+        let type_hash = tx
+            .outputs()
+            .get(index)
+            .and_then(|output| output.type_().to_opt())
+            .map(|script| script.calc_script_hash().unpack());
+        Ok(DeployedScript {
+            tx_hash,
+            index: index as u32,
+            data_hash: blake2b_256(code).into(),
+            type_hash,
+            dep_type: DepType::Code,
+            history: vec![],
+        })
+    }
+
+    pub fn wait_committed(&self, tx_hash: &H256) -> ScriptResult<()> {
+        let start = Instant::now();
+        loop {
+            if let Some(tx) = self.rpc.get_transaction(tx_hash)? {
+                match tx.tx_status.status {
+                    Status::Committed => return Ok(()),
+                    Status::Rejected => {
+                        return Err(ScriptError::Rejected(
+                            tx_hash.clone(),
+                            tx.tx_status.reason.unwrap_or_default(),
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+            if start.elapsed() >= self.timeout {
+                return Err(ScriptError::NotCommitted {
+                    tx_hash: tx_hash.clone(),
+                    waited_secs: self.timeout.as_secs(),
+                });
+            }
+            thread::sleep(COMMIT_POLL_INTERVAL);
+        }
+    }
+}
+
+fn type_id_script(args: [u8; 32]) -> packed::Script {
+    packed::Script::new_builder()
+        .code_hash(TYPE_ID_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(Bytes::from(args.to_vec()).pack())
+        .build()
+}
+
+// Sets the args of the Type ID on output `index` from the transaction's first input,
+// as the Type ID script requires when a new ID is created
+fn with_type_id(tx: TransactionView, index: usize) -> TransactionView {
+    let first_input = tx.inputs().get(0).expect("built transactions have inputs");
+    let mut hasher = new_blake2b();
+    hasher.update(first_input.as_slice());
+    hasher.update(&(index as u64).to_le_bytes());
+    let mut args = [0u8; 32];
+    hasher.finalize(&mut args);
+
+    let mut outputs = tx.outputs().into_iter().collect::<Vec<_>>();
+    outputs[index] = outputs[index]
+        .clone()
+        .as_builder()
+        .type_(Some(type_id_script(args)).pack())
+        .build();
+    tx.as_advanced_builder().set_outputs(outputs).build()
+}
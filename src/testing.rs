@@ -0,0 +1,517 @@
+use crate::indexer::Cell;
+use crate::project::TrampolineProject;
+use crate::script::{BuildProfile, Script, ScriptError};
+use crate::transaction::{
+    CellSource, GenesisInfo, TransactionBuilder, TransactionError, TransactionResult,
+};
+
+use ckb_chain_spec::consensus::ConsensusBuilder;
+use ckb_hash::blake2b_256;
+use ckb_jsonrpc_types::JsonBytes;
+use ckb_script::{ScriptGroupType, TransactionScriptsVerifier, TxVerifyEnv};
+use ckb_traits::{CellDataProvider, HeaderProvider};
+use ckb_types::{
+    bytes::Bytes,
+    core::{
+        cell::{CellMeta, CellMetaBuilder, ResolvedTransaction},
+        Capacity, Cycle, DepType, EpochNumberWithFraction, HeaderView, ScriptHashType,
+        TransactionView,
+    },
+    packed,
+    prelude::*,
+    H256,
+};
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use thiserror::Error;
+
+/// Cycle limit of each script group unless a test passes its own.
+pub const DEFAULT_MAX_CYCLES: Cycle = 70_000_000;
+/// Set by `trampoline script test` to the build profile the binaries were built with.
+pub const PROFILE_ENV: &str = "TRAMPOLINE_TEST_PROFILE";
+
+#[derive(Debug, Error)]
+pub enum TestingError {
+    #[error(transparent)]
+    Script(#[from] ScriptError),
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+    #[error("Cell {0} is not in the context")]
+    UnknownCell(String),
+    #[error("Cell {0} is used as a dep group but does not hold a list of out points")]
+    InvalidDepGroup(String),
+}
+
+pub type TestingResult<T> = std::result::Result<T, TestingError>;
+
+/// The build profile selected by `trampoline script test --release`, debug otherwise.
+pub fn test_profile() -> BuildProfile {
+    match std::env::var(PROFILE_ENV).as_deref() {
+        Ok("release") => BuildProfile::Release,
+        _ => BuildProfile::Debug,
+    }
+}
+
+/// Stands in for the chain when verifying transactions offline: it holds the cells and
+/// headers scripts can load, and serves them to the CKB VM as its data loader and to
+/// `TransactionBuilder` as its cell source.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    cells: HashMap<packed::OutPoint, (packed::CellOutput, Bytes)>,
+    headers: HashMap<packed::Byte32, HeaderView>,
+    // Code cells by the data hash scripts reference them with
+    binaries: HashMap<packed::Byte32, packed::OutPoint>,
+    // Fake transaction hashes are derived from it, so out points are stable between runs
+    created: u64,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Puts `code` in a new cell that `complete` adds as a cell dep wherever it is used.
+    pub fn deploy(&mut self, code: Bytes) -> packed::OutPoint {
+        let data_hash = packed::CellOutput::calc_data_hash(&code);
+        let output = packed::CellOutput::new_builder().build();
+        let capacity = output
+            .occupied_capacity(Capacity::bytes(code.len()).expect("binary size"))
+            .expect("binary capacity");
+        let output = output.as_builder().capacity(capacity.pack()).build();
+        let out_point = self.create_cell(output, code);
+        self.binaries.insert(data_hash, out_point.clone());
+        out_point
+    }
+
+    /// Deploys the `profile` build of the script `name` under `scripts/`.
+    pub fn deploy_script(
+        &mut self,
+        project: &TrampolineProject,
+        name: &str,
+        profile: BuildProfile,
+    ) -> TestingResult<packed::OutPoint> {
+        let code = Script::load(project, name)?.code(project, profile)?;
+        Ok(self.deploy(code.into()))
+    }
+
+    /// A script running the code deployed at `out_point`, referenced by data hash.
+    pub fn build_script(
+        &self,
+        out_point: &packed::OutPoint,
+        args: Bytes,
+    ) -> Option<packed::Script> {
+        let (_, code) = self.cells.get(out_point)?;
+        Some(
+            packed::Script::new_builder()
+                .code_hash(packed::CellOutput::calc_data_hash(code))
+                .hash_type(ScriptHashType::Data.into())
+                .args(args.pack())
+                .build(),
+        )
+    }
+
+    /// Adds a live cell, to be spent or loaded by the transactions under test.
+    pub fn create_cell(&mut self, output: packed::CellOutput, data: Bytes) -> packed::OutPoint {
+        self.created += 1;
+        let out_point = created_out_point(self.created);
+        self.cells.insert(out_point.clone(), (output, data));
+        out_point
+    }
+
+    pub fn insert_header(&mut self, header: HeaderView) {
+        self.headers.insert(header.hash(), header);
+    }
+
+    /// A `TransactionBuilder` collecting inputs from the cells of this context. `build`
+    /// completes its transaction.
+    pub fn transaction(&self) -> TransactionBuilder<&Self> {
+        TransactionBuilder::with_source(self)
+    }
+
+    /// Balances the transaction of `builder` with cells of this context, then completes it.
+    pub fn build(&self, builder: TransactionBuilder<&Self>) -> TestingResult<TransactionView> {
+        Ok(self.complete(builder.build()?))
+    }
+
+    /// Adds cell deps for the deployed binaries that the scripts of the inputs and outputs
+    /// reference, and pads the witnesses to one per input.
+    pub fn complete(&self, tx: TransactionView) -> TransactionView {
+        let mut cell_deps = tx.cell_deps_iter().collect::<Vec<_>>();
+        let outputs = tx
+            .input_pts_iter()
+            .filter_map(|out_point| self.cells.get(&out_point))
+            .map(|(output, _)| output.clone())
+            .chain(tx.outputs());
+        for output in outputs {
+            for script in Some(output.lock())
+                .into_iter()
+                .chain(output.type_().to_opt())
+            {
+                if let Some(out_point) = self.binaries.get(&script.code_hash()) {
+                    let cell_dep = packed::CellDep::new_builder()
+                        .out_point(out_point.clone())
+                        .dep_type(DepType::Code.into())
+                        .build();
+                    if !cell_deps.contains(&cell_dep) {
+                        cell_deps.push(cell_dep);
+                    }
+                }
+            }
+        }
+
+        let mut witnesses = tx.witnesses().into_iter().collect::<Vec<_>>();
+        if witnesses.len() < tx.inputs().len() {
+            witnesses.resize(tx.inputs().len(), Bytes::new().pack());
+        }
+        tx.as_advanced_builder()
+            .set_cell_deps(cell_deps)
+            .set_witnesses(witnesses)
+            .build()
+    }
+
+    /// Looks up the cells the inputs and cell deps of `tx` point to.
+    pub fn resolve(&self, tx: &TransactionView) -> TestingResult<ResolvedTransaction> {
+        let resolved_inputs = tx
+            .input_pts_iter()
+            .map(|out_point| self.cell_meta(&out_point))
+            .collect::<TestingResult<Vec<_>>>()?;
+        let mut resolved_cell_deps = vec![];
+        let mut resolved_dep_groups = vec![];
+        let dep_group: packed::Byte = DepType::DepGroup.into();
+        for cell_dep in tx.cell_deps_iter() {
+            let out_point = cell_dep.out_point();
+            let cell = self.cell_meta(&out_point)?;
+            if cell_dep.dep_type() == dep_group {
+                let (_, data) = &self.cells[&out_point];
+                let members = packed::OutPointVec::from_slice(data)
+                    .map_err(|_| TestingError::InvalidDepGroup(describe(&out_point)))?;
+                for member in members.into_iter() {
+                    resolved_cell_deps.push(self.cell_meta(&member)?);
+                }
+                resolved_dep_groups.push(cell);
+            } else {
+                resolved_cell_deps.push(cell);
+            }
+        }
+        Ok(ResolvedTransaction {
+            transaction: tx.clone(),
+            resolved_cell_deps,
+            resolved_inputs,
+            resolved_dep_groups,
+        })
+    }
+
+    /// Runs every lock and type script group of `tx` in the CKB VM, each limited to
+    /// `max_cycles`. Failing scripts are reported in the result rather than as an error,
+    /// and their debug output is printed as they run.
+    pub fn verify(&self, tx: &TransactionView, max_cycles: Cycle) -> TestingResult<Verification> {
+        let rtx = self.resolve(tx)?;
+        let consensus = ConsensusBuilder::default().build();
+        let tx_env = TxVerifyEnv::new_commit(&HeaderView::new_advanced_builder().build());
+        let mut verifier = TransactionScriptsVerifier::new(&rtx, &consensus, self, &tx_env);
+        verifier.set_debug_printer(|script_hash, message| {
+            let script_hash: H256 = script_hash.unpack();
+            println!("[{:#x}] {}", script_hash, message);
+        });
+
+        let groups = script_groups(&rtx)
+            .into_iter()
+            .map(|(script_type, script_hash)| GroupVerification {
+                script_type,
+                result: verifier
+                    .verify_single(script_type, &script_hash, max_cycles)
+                    .map_err(|e| e.to_string()),
+                script_hash: script_hash.unpack(),
+            })
+            .collect();
+        Ok(Verification { groups })
+    }
+
+    fn cell_meta(&self, out_point: &packed::OutPoint) -> TestingResult<CellMeta> {
+        let (output, data) = self
+            .cells
+            .get(out_point)
+            .ok_or_else(|| TestingError::UnknownCell(describe(out_point)))?;
+        Ok(
+            CellMetaBuilder::from_cell_output(output.clone(), data.clone())
+                .out_point(out_point.clone())
+                .build(),
+        )
+    }
+}
+
+impl CellDataProvider for Context {
+    fn get_cell_data(&self, out_point: &packed::OutPoint) -> Option<Bytes> {
+        self.cells.get(out_point).map(|(_, data)| data.clone())
+    }
+
+    fn get_cell_data_hash(&self, out_point: &packed::OutPoint) -> Option<packed::Byte32> {
+        self.cells
+            .get(out_point)
+            .map(|(_, data)| packed::CellOutput::calc_data_hash(data))
+    }
+}
+
+// Cells are collected in the order they were created. The context has no system
+// scripts, and its cells count as committed in the genesis block.
+impl CellSource for Context {
+    fn live_cell(
+        &self,
+        out_point: &packed::OutPoint,
+    ) -> TransactionResult<Option<packed::CellOutput>> {
+        Ok(self.cells.get(out_point).map(|(output, _)| output.clone()))
+    }
+
+    fn cells_by_lock<'s>(
+        &'s self,
+        lock: &packed::Script,
+    ) -> Box<dyn Iterator<Item = TransactionResult<Cell>> + 's> {
+        let lock = lock.clone();
+        let cells = (1..=self.created).filter_map(move |created| {
+            let out_point = created_out_point(created);
+            let (output, data) = self.cells.get(&out_point)?;
+            (output.lock() == lock).then(|| {
+                Ok(Cell {
+                    output: output.clone().into(),
+                    output_data: JsonBytes::from_bytes(data.clone()),
+                    out_point: out_point.into(),
+                    block_number: 0.into(),
+                    tx_index: 0.into(),
+                })
+            })
+        });
+        Box::new(cells)
+    }
+
+    fn genesis(&self) -> TransactionResult<Option<GenesisInfo>> {
+        Ok(None)
+    }
+
+    fn tip_header(&self) -> TransactionResult<HeaderView> {
+        Ok(self
+            .headers
+            .values()
+            .max_by_key(|header| header.number())
+            .cloned()
+            .unwrap_or_else(|| HeaderView::new_advanced_builder().build()))
+    }
+
+    fn header_by_number(&self, number: u64) -> TransactionResult<Option<HeaderView>> {
+        let header = self
+            .headers
+            .values()
+            .find(|header| header.number() == number)
+            .cloned();
+        Ok(header.or_else(|| (number == 0).then(|| HeaderView::new_advanced_builder().build())))
+    }
+
+    fn cellbase_maturity(&self) -> TransactionResult<EpochNumberWithFraction> {
+        Ok(ConsensusBuilder::default().build().cellbase_maturity())
+    }
+}
+
+impl HeaderProvider for Context {
+    fn get_header(&self, hash: &packed::Byte32) -> Option<HeaderView> {
+        self.headers.get(hash).cloned()
+    }
+}
+
+/// How one script group fared: the cycles it consumed, or the error it failed with.
+#[derive(Debug, Clone)]
+pub struct GroupVerification {
+    pub script_type: ScriptGroupType,
+    pub script_hash: H256,
+    pub result: Result<Cycle, String>,
+}
+
+impl std::fmt::Display for GroupVerification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let script_type = match self.script_type {
+            ScriptGroupType::Lock => "lock",
+            ScriptGroupType::Type => "type",
+        };
+        match &self.result {
+            Ok(cycles) => write!(
+                f,
+                "{} {:#x}: {} cycles",
+                script_type, self.script_hash, cycles
+            ),
+            Err(error) => write!(
+                f,
+                "{} {:#x}: failed: {}",
+                script_type, self.script_hash, error
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Verification {
+    pub groups: Vec<GroupVerification>,
+}
+
+impl Verification {
+    pub fn is_success(&self) -> bool {
+        self.groups.iter().all(|group| group.result.is_ok())
+    }
+
+    /// Cycles consumed by the groups that succeeded.
+    pub fn cycles(&self) -> Cycle {
+        self.groups
+            .iter()
+            .filter_map(|group| group.result.as_ref().ok())
+            .sum()
+    }
+}
+
+impl std::fmt::Display for Verification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for group in &self.groups {
+            writeln!(f, "{}", group)?;
+        }
+        write!(f, "total: {} cycles", self.cycles())
+    }
+}
+
+// Lock groups of the inputs, then type groups of the inputs and outputs
+fn script_groups(rtx: &ResolvedTransaction) -> Vec<(ScriptGroupType, packed::Byte32)> {
+    let mut groups = vec![];
+    for input in &rtx.resolved_inputs {
+        let group = (ScriptGroupType::Lock, input.cell_output.calc_lock_hash());
+        if !groups.contains(&group) {
+            groups.push(group);
+        }
+    }
+    let types = rtx
+        .resolved_inputs
+        .iter()
+        .map(|input| input.cell_output.type_())
+        .chain(
+            rtx.transaction
+                .outputs()
+                .into_iter()
+                .map(|output| output.type_()),
+        );
+    for type_ in types {
+        if let Some(script) = type_.to_opt() {
+            let group = (ScriptGroupType::Type, script.calc_script_hash());
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+    }
+    groups
+}
+
+fn created_out_point(created: u64) -> packed::OutPoint {
+    packed::OutPoint::new(blake2b_256(created.to_le_bytes()).pack(), 0)
+}
+
+fn describe(out_point: &packed::OutPoint) -> String {
+    let tx_hash: H256 = out_point.tx_hash().unpack();
+    let index: u32 = out_point.index().unpack();
+    format!("{:#x}:{}", tx_hash, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_types::core::TransactionBuilder;
+
+    const ONE_CKB: u64 = 100_000_000;
+
+    // ELF for CKB-VM running `li a0, 0; li a7, 93; ecall`, so it exits with 0
+    fn always_success() -> Bytes {
+        let entry: u64 = 0x10000 + 64 + 56;
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        elf.extend(2u16.to_le_bytes()); // executable
+        elf.extend(243u16.to_le_bytes()); // RISC-V
+        elf.extend(1u32.to_le_bytes());
+        elf.extend(entry.to_le_bytes());
+        elf.extend(64u64.to_le_bytes()); // program headers
+        elf.extend(0u64.to_le_bytes()); // section headers
+        elf.extend(0u32.to_le_bytes());
+        for half in [64u16, 56, 1, 64, 0, 0] {
+            elf.extend(half.to_le_bytes());
+        }
+        let size: u64 = 64 + 56 + 12;
+        elf.extend(1u32.to_le_bytes()); // PT_LOAD
+        elf.extend(5u32.to_le_bytes()); // readable and executable
+        for word in [0u64, 0x10000, 0x10000, size, size, 0x1000] {
+            elf.extend(word.to_le_bytes());
+        }
+        for instruction in [0x0000_0513u32, 0x05d0_0893, 0x0000_0073] {
+            elf.extend(instruction.to_le_bytes());
+        }
+        Bytes::from(elf)
+    }
+
+    #[test]
+    fn test_build_with_context() {
+        let mut context = Context::new();
+        let code = context.deploy(always_success());
+        let lock = context.build_script(&code, Bytes::new()).unwrap();
+        let cell = packed::CellOutput::new_builder()
+            .capacity(Capacity::shannons(1000 * ONE_CKB).pack())
+            .lock(lock.clone())
+            .build();
+        let input = context.create_cell(cell, Bytes::new());
+        let output = packed::CellOutput::new_builder()
+            .capacity(Capacity::shannons(100 * ONE_CKB).pack())
+            .lock(lock.clone())
+            .build();
+
+        let builder = context
+            .transaction()
+            .from_lock(lock.clone())
+            .output(output, Bytes::new());
+        let tx = context.build(builder).unwrap();
+        assert_eq!(tx.input_pts_iter().collect::<Vec<_>>(), vec![input]);
+        assert_eq!(tx.cell_deps().len(), 1);
+        assert_eq!(tx.outputs().len(), 2);
+        let change_capacity: u64 = tx.outputs().get(1).unwrap().capacity().unpack();
+        assert!(change_capacity < 900 * ONE_CKB);
+
+        let verification = context.verify(&tx, DEFAULT_MAX_CYCLES).unwrap();
+        assert!(verification.is_success(), "{}", verification);
+        assert_eq!(verification.groups.len(), 1);
+    }
+
+    #[test]
+    fn test_verify_reports_failing_group() {
+        let mut context = Context::new();
+        let code = context.deploy(Bytes::from_static(b"not an elf binary"));
+        let lock = context.build_script(&code, Bytes::new()).unwrap();
+        let cell = packed::CellOutput::new_builder()
+            .capacity(Capacity::shannons(1000).pack())
+            .lock(lock.clone())
+            .build();
+        let input = context.create_cell(cell.clone(), Bytes::new());
+        let tx = TransactionBuilder::default()
+            .input(packed::CellInput::new(input, 0))
+            .output(cell)
+            .output_data(Bytes::new().pack())
+            .build();
+        let tx = context.complete(tx);
+        assert_eq!(tx.cell_deps().len(), 1);
+        assert_eq!(tx.witnesses().len(), 1);
+
+        let verification = context.verify(&tx, DEFAULT_MAX_CYCLES).unwrap();
+        assert!(!verification.is_success());
+        assert_eq!(verification.groups.len(), 1);
+        let group = &verification.groups[0];
+        assert_eq!(group.script_hash, lock.calc_script_hash().unpack());
+        assert!(group.result.is_err());
+
+        let unknown = TransactionBuilder::default()
+            .input(packed::CellInput::new(
+                packed::OutPoint::new(Default::default(), 1),
+                0,
+            ))
+            .build();
+        assert!(matches!(
+            context.verify(&unknown, DEFAULT_MAX_CYCLES),
+            Err(TestingError::UnknownCell(_))
+        ));
+    }
+}
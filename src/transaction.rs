@@ -0,0 +1,51 @@
+mod builder;
+mod genesis;
+mod signer;
+
+pub use builder::{CellSource, NodeSource, TransactionBuilder, DEFAULT_FEE_RATE};
+pub use genesis::GenesisInfo;
+pub use signer::{input_locks, sighash_all_message, MultisigConfig, Signer, SIGNATURE_SIZE};
+
+use crate::indexer::IndexerError;
+use crate::rpc::RpcError;
+
+use ckb_types::core::CapacityError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TransactionError {
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    #[error(transparent)]
+    Indexer(#[from] IndexerError),
+    #[error(transparent)]
+    Capacity(#[from] CapacityError),
+    #[error(transparent)]
+    Secp(#[from] ckb_crypto::secp::Error),
+    #[error("Unexpected genesis block: {0}")]
+    InvalidGenesis(String),
+    #[error("Input {0} is not a live cell")]
+    DeadCell(String),
+    #[error("Output {index} holds {capacity} shannons but occupies {occupied}")]
+    OutputTooSmall {
+        index: usize,
+        capacity: u64,
+        occupied: u64,
+    },
+    #[error("Inputs hold {available} shannons but {required} are needed")]
+    InsufficientCapacity { available: u64, required: u64 },
+    #[error("No lock to collect inputs from or send change to")]
+    NoChangeLock,
+    #[error("Missing header of block {0}")]
+    MissingHeader(u64),
+    #[error("Cannot find the cell spent by input {0}")]
+    UnknownInput(String),
+    #[error("Invalid witness: {0}")]
+    InvalidWitness(String),
+    #[error("Invalid multisig configuration: {0}")]
+    InvalidMultisig(String),
+    #[error("No input can be unlocked by the key with lock arg {0}")]
+    NothingToSign(String),
+}
+
+pub type TransactionResult<T> = std::result::Result<T, TransactionError>;
@@ -0,0 +1,708 @@
+use super::{GenesisInfo, TransactionError, TransactionResult, SIGNATURE_SIZE};
+use crate::indexer::{BlockingIndexerClient, Cell, Order, SearchKey};
+use crate::rpc::BlockingRpcClient;
+
+use ckb_types::{
+    bytes::Bytes,
+    core::{self, Capacity, EpochNumberWithFraction, FeeRate, ScriptHashType, TransactionView},
+    packed,
+    prelude::*,
+    H256,
+};
+
+/// Minimum fee rate accepted by ckb nodes, in shannons per 1000 bytes.
+pub const DEFAULT_FEE_RATE: u64 = 1000;
+
+// Multisig lock args may carry a since after the 20-byte hash
+const MULTISIG_SINCE_ARGS_SIZE: usize = 28;
+const SINCE_RELATIVE_FLAG: u64 = 1 << 63;
+const SINCE_METRIC_MASK: u64 = 0b11 << 61;
+const SINCE_METRIC_BLOCK_NUMBER: u64 = 0;
+const SINCE_METRIC_EPOCH: u64 = 0b01 << 61;
+const SINCE_RESERVED_MASK: u64 = 0x1f << 56;
+const SINCE_VALUE_MASK: u64 = (1 << 56) - 1;
+
+/// Where `TransactionBuilder` finds the cells it spends, and the chain state that decides
+/// whether they can be spent yet.
+pub trait CellSource {
+    /// Output of the cell at `out_point`, or `None` unless it is live.
+    fn live_cell(
+        &self,
+        out_point: &packed::OutPoint,
+    ) -> TransactionResult<Option<packed::CellOutput>>;
+
+    /// Live cells whose lock is `lock`, oldest first. Sources may also return cells whose
+    /// lock args merely start with those of `lock`.
+    fn cells_by_lock<'s>(
+        &'s self,
+        lock: &packed::Script,
+    ) -> Box<dyn Iterator<Item = TransactionResult<Cell>> + 's>;
+
+    /// System scripts of the chain, or `None` when it has none.
+    fn genesis(&self) -> TransactionResult<Option<GenesisInfo>>;
+
+    fn tip_header(&self) -> TransactionResult<core::HeaderView>;
+
+    fn header_by_number(&self, number: u64) -> TransactionResult<Option<core::HeaderView>>;
+
+    /// Epochs before a cellbase output can be spent.
+    fn cellbase_maturity(&self) -> TransactionResult<EpochNumberWithFraction>;
+}
+
+impl<S: CellSource + ?Sized> CellSource for &S {
+    fn live_cell(
+        &self,
+        out_point: &packed::OutPoint,
+    ) -> TransactionResult<Option<packed::CellOutput>> {
+        (**self).live_cell(out_point)
+    }
+
+    fn cells_by_lock<'s>(
+        &'s self,
+        lock: &packed::Script,
+    ) -> Box<dyn Iterator<Item = TransactionResult<Cell>> + 's> {
+        (**self).cells_by_lock(lock)
+    }
+
+    fn genesis(&self) -> TransactionResult<Option<GenesisInfo>> {
+        (**self).genesis()
+    }
+
+    fn tip_header(&self) -> TransactionResult<core::HeaderView> {
+        (**self).tip_header()
+    }
+
+    fn header_by_number(&self, number: u64) -> TransactionResult<Option<core::HeaderView>> {
+        (**self).header_by_number(number)
+    }
+
+    fn cellbase_maturity(&self) -> TransactionResult<EpochNumberWithFraction> {
+        (**self).cellbase_maturity()
+    }
+}
+
+/// A node's RPC, with the indexer collecting cells by lock.
+pub struct NodeSource<'a> {
+    rpc: &'a BlockingRpcClient,
+    indexer: &'a BlockingIndexerClient,
+}
+
+impl<'a> NodeSource<'a> {
+    pub fn new(rpc: &'a BlockingRpcClient, indexer: &'a BlockingIndexerClient) -> Self {
+        NodeSource { rpc, indexer }
+    }
+}
+
+impl CellSource for NodeSource<'_> {
+    fn live_cell(
+        &self,
+        out_point: &packed::OutPoint,
+    ) -> TransactionResult<Option<packed::CellOutput>> {
+        let status = self.rpc.get_live_cell(&out_point.clone().into(), false)?;
+        Ok(status
+            .cell
+            .filter(|_| status.status == "live")
+            .map(|cell| cell.output.into()))
+    }
+
+    fn cells_by_lock<'s>(
+        &'s self,
+        lock: &packed::Script,
+    ) -> Box<dyn Iterator<Item = TransactionResult<Cell>> + 's> {
+        let cells = self
+            .indexer
+            .cells(SearchKey::lock(lock.clone().into()), Order::Asc);
+        Box::new(cells.map(|cell| cell.map_err(TransactionError::from)))
+    }
+
+    fn genesis(&self) -> TransactionResult<Option<GenesisInfo>> {
+        GenesisInfo::load(self.rpc).map(Some)
+    }
+
+    fn tip_header(&self) -> TransactionResult<core::HeaderView> {
+        Ok(self.rpc.get_tip_header()?.into())
+    }
+
+    fn header_by_number(&self, number: u64) -> TransactionResult<Option<core::HeaderView>> {
+        Ok(self.rpc.get_header_by_number(number)?.map(Into::into))
+    }
+
+    fn cellbase_maturity(&self) -> TransactionResult<EpochNumberWithFraction> {
+        let consensus = self.rpc.get_consensus()?;
+        Ok(EpochNumberWithFraction::from_full_value(
+            consensus.cellbase_maturity.value(),
+        ))
+    }
+}
+
+/// Chain state needed to tell whether a collected cell can already be spent.
+struct ChainTip {
+    number: u64,
+    epoch: EpochNumberWithFraction,
+    cellbase_maturity: EpochNumberWithFraction,
+}
+
+impl ChainTip {
+    fn load(source: &impl CellSource) -> TransactionResult<Self> {
+        let header = source.tip_header()?;
+        Ok(ChainTip {
+            number: header.number(),
+            epoch: header.epoch(),
+            cellbase_maturity: source.cellbase_maturity()?,
+        })
+    }
+}
+
+/// Completes a partial transaction so it can be signed: collects inputs from its
+/// `CellSource` until the outputs and the fee are covered, adds a change output and the
+/// cell deps of the system locks it spends, and reserves witness space for the signatures.
+pub struct TransactionBuilder<S> {
+    source: S,
+    genesis: Option<GenesisInfo>,
+    tx: TransactionView,
+    senders: Vec<packed::Script>,
+    change_lock: Option<packed::Script>,
+    fee_rate: FeeRate,
+    witness_lock_sizes: Vec<(packed::Script, usize)>,
+}
+
+impl<'a> TransactionBuilder<NodeSource<'a>> {
+    pub fn new(rpc: &'a BlockingRpcClient, indexer: &'a BlockingIndexerClient) -> Self {
+        Self::with_source(NodeSource::new(rpc, indexer))
+    }
+}
+
+impl<S: CellSource> TransactionBuilder<S> {
+    pub fn with_source(source: S) -> Self {
+        TransactionBuilder {
+            source,
+            genesis: None,
+            tx: core::TransactionBuilder::default().build(),
+            senders: vec![],
+            change_lock: None,
+            fee_rate: FeeRate::from_u64(DEFAULT_FEE_RATE),
+            witness_lock_sizes: vec![],
+        }
+    }
+
+    /// Starts from an existing transaction instead of an empty one.
+    pub fn base(mut self, tx: TransactionView) -> Self {
+        self.tx = tx;
+        self
+    }
+
+    /// Skips fetching the genesis block when it is already known.
+    pub fn genesis(mut self, genesis: GenesisInfo) -> Self {
+        self.genesis = Some(genesis);
+        self
+    }
+
+    /// Spends a specific cell, in addition to those collected from the senders.
+    pub fn input(mut self, out_point: packed::OutPoint) -> Self {
+        self.tx = self
+            .tx
+            .as_advanced_builder()
+            .input(packed::CellInput::new(out_point, 0))
+            .build();
+        self
+    }
+
+    pub fn output(mut self, output: packed::CellOutput, data: Bytes) -> Self {
+        self.tx = self
+            .tx
+            .as_advanced_builder()
+            .output(output)
+            .output_data(data.pack())
+            .build();
+        self
+    }
+
+    pub fn cell_dep(mut self, cell_dep: packed::CellDep) -> Self {
+        self.tx = self.tx.as_advanced_builder().cell_dep(cell_dep).build();
+        self
+    }
+
+    /// Collects plain capacity cells locked by `lock` as inputs. The first sender also
+    /// receives the change unless `change_lock` is set.
+    pub fn from_lock(mut self, lock: packed::Script) -> Self {
+        self.senders.push(lock);
+        self
+    }
+
+    pub fn change_lock(mut self, lock: packed::Script) -> Self {
+        self.change_lock = Some(lock);
+        self
+    }
+
+    /// Fee rate in shannons per 1000 bytes.
+    pub fn fee_rate(mut self, shannons_per_kb: u64) -> Self {
+        self.fee_rate = FeeRate::from_u64(shannons_per_kb);
+        self
+    }
+
+    /// Witness lock size reserved for inputs locked by `lock`, when it is not a
+    /// single secp256k1 signature (multisig for example).
+    pub fn witness_lock_size(mut self, lock: packed::Script, size: usize) -> Self {
+        self.witness_lock_sizes.push((lock, size));
+        self
+    }
+
+    pub fn build(self) -> TransactionResult<TransactionView> {
+        let genesis = match &self.genesis {
+            Some(genesis) => Some(genesis.clone()),
+            None => self.source.genesis()?,
+        };
+        let genesis = genesis.as_ref();
+        let change_lock = self
+            .change_lock
+            .clone()
+            .or_else(|| self.senders.first().cloned())
+            .ok_or(TransactionError::NoChangeLock)?;
+
+        let mut output_capacity = 0;
+        for (index, (output, data)) in self.tx.outputs_with_data_iter().enumerate() {
+            let capacity: u64 = output.capacity().unpack();
+            let occupied = output
+                .occupied_capacity(Capacity::bytes(data.len())?)?
+                .as_u64();
+            if capacity < occupied {
+                return Err(TransactionError::OutputTooSmall {
+                    index,
+                    capacity,
+                    occupied,
+                });
+            }
+            output_capacity += capacity;
+        }
+
+        let mut inputs = vec![];
+        let mut input_capacity = 0;
+        for input in self.tx.inputs() {
+            let out_point = input.previous_output();
+            let output = self
+                .source
+                .live_cell(&out_point)?
+                .ok_or_else(|| TransactionError::DeadCell(describe(&out_point)))?;
+            let capacity: u64 = output.capacity().unpack();
+            input_capacity += capacity;
+            inputs.push((input, output.lock()));
+        }
+
+        // The indexer matches lock args by prefix, so cells are checked against the senders
+        let mut tip = None;
+        let mut candidates = self
+            .senders
+            .iter()
+            .flat_map(|lock| self.source.cells_by_lock(lock));
+        let change = packed::CellOutput::new_builder().lock(change_lock).build();
+        let change_occupied = change.occupied_capacity(Capacity::zero())?.as_u64();
+        loop {
+            let with_change = self.assemble(genesis, &inputs, Some(change.clone()));
+            let required = output_capacity + self.fee(&with_change);
+            if input_capacity >= required + change_occupied {
+                let change = change
+                    .as_builder()
+                    .capacity(Capacity::shannons(input_capacity - required).pack())
+                    .build();
+                return Ok(self.assemble(genesis, &inputs, Some(change)));
+            }
+
+            match candidates.next() {
+                Some(cell) => {
+                    let cell = cell?;
+                    let out_point = packed::OutPoint::from(cell.out_point.clone());
+                    let lock = packed::Script::from(cell.output.lock.clone());
+                    // Cells with a type script or data belong to some contract
+                    let plain = cell.output.type_.is_none() && cell.output_data.is_empty();
+                    let spent = inputs
+                        .iter()
+                        .any(|(input, _)| input.previous_output() == out_point);
+                    if !plain || spent || !self.senders.contains(&lock) {
+                        continue;
+                    }
+                    if let Some(since) = self.unlocked_since(genesis, &cell, &lock, &mut tip)? {
+                        input_capacity += cell.output.capacity.value();
+                        inputs.push((packed::CellInput::new(out_point, since), lock));
+                    }
+                }
+                None => {
+                    // Too little left for a change cell, so the surplus goes to the miner
+                    let without_change = self.assemble(genesis, &inputs, None);
+                    let required = output_capacity + self.fee(&without_change);
+                    if input_capacity >= required {
+                        return Ok(without_change);
+                    }
+                    return Err(TransactionError::InsufficientCapacity {
+                        available: input_capacity,
+                        required,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Since of the input spending `cell`, or `None` while the cell cannot be spent yet:
+    /// cellbase outputs need `cellbase_maturity` epochs, and multisig locks can hold a
+    /// since in their args.
+    fn unlocked_since(
+        &self,
+        genesis: Option<&GenesisInfo>,
+        cell: &Cell,
+        lock: &packed::Script,
+        tip: &mut Option<ChainTip>,
+    ) -> TransactionResult<Option<u64>> {
+        let block_number = cell.block_number.value();
+        // Genesis cellbase outputs are spendable right away
+        let cellbase = cell.tx_index.value() == 0 && block_number > 0;
+        let since = lock_since(genesis, lock);
+        if !cellbase && since.is_none() {
+            return Ok(Some(0));
+        }
+
+        let tip = match tip {
+            Some(tip) => tip,
+            None => tip.insert(ChainTip::load(&self.source)?),
+        };
+        let epoch = self
+            .source
+            .header_by_number(block_number)?
+            .ok_or(TransactionError::MissingHeader(block_number))?
+            .epoch();
+        if cellbase
+            && tip.epoch.to_rational() < epoch.to_rational() + tip.cellbase_maturity.to_rational()
+        {
+            return Ok(None);
+        }
+        match since {
+            Some(since) if !since_passed(since, block_number, epoch, tip) => Ok(None),
+            since => Ok(Some(since.unwrap_or(0))),
+        }
+    }
+
+    fn fee(&self, tx: &TransactionView) -> u64 {
+        self.fee_rate
+            .fee(tx.data().serialized_size_in_block())
+            .as_u64()
+    }
+
+    fn assemble(
+        &self,
+        genesis: Option<&GenesisInfo>,
+        inputs: &[(packed::CellInput, packed::Script)],
+        change: Option<packed::CellOutput>,
+    ) -> TransactionView {
+        let mut cell_deps = self.tx.cell_deps_iter().collect::<Vec<_>>();
+        for (_, lock) in inputs {
+            if let Some(cell_dep) = genesis.and_then(|genesis| genesis.cell_dep(lock)) {
+                if !cell_deps.contains(&cell_dep) {
+                    cell_deps.push(cell_dep);
+                }
+            }
+        }
+
+        let mut outputs = self.tx.outputs().into_iter().collect::<Vec<_>>();
+        let mut outputs_data = self.tx.outputs_data().into_iter().collect::<Vec<_>>();
+        if let Some(change) = change {
+            outputs.push(change);
+            outputs_data.push(Bytes::new().pack());
+        }
+
+        // The first input of each lock group carries the signature
+        let mut witnesses = self.tx.witnesses().into_iter().collect::<Vec<_>>();
+        if witnesses.len() < inputs.len() {
+            witnesses.resize(inputs.len(), Bytes::new().pack());
+        }
+        let mut groups: Vec<&packed::Script> = vec![];
+        for (index, (_, lock)) in inputs.iter().enumerate() {
+            if groups.contains(&lock) {
+                continue;
+            }
+            groups.push(lock);
+            if witnesses[index].is_empty() {
+                let placeholder = packed::WitnessArgs::new_builder()
+                    .lock(Some(Bytes::from(vec![0u8; self.lock_size(lock)])).pack())
+                    .build();
+                witnesses[index] = placeholder.as_bytes().pack();
+            }
+        }
+
+        self.tx
+            .as_advanced_builder()
+            .set_cell_deps(cell_deps)
+            .set_inputs(inputs.iter().map(|(input, _)| input.clone()).collect())
+            .set_outputs(outputs)
+            .set_outputs_data(outputs_data)
+            .set_witnesses(witnesses)
+            .build()
+    }
+
+    fn lock_size(&self, lock: &packed::Script) -> usize {
+        self.witness_lock_sizes
+            .iter()
+            .find(|(script, _)| script == lock)
+            .map(|(_, size)| *size)
+            .unwrap_or(SIGNATURE_SIZE)
+    }
+}
+
+fn lock_since(genesis: Option<&GenesisInfo>, lock: &packed::Script) -> Option<u64> {
+    let genesis = genesis?;
+    let type_hash_type: packed::Byte = ScriptHashType::Type.into();
+    let code_hash: H256 = lock.code_hash().unpack();
+    let args = lock.args().raw_data();
+    if lock.hash_type() != type_hash_type
+        || code_hash != genesis.multisig_type_hash
+        || args.len() != MULTISIG_SINCE_ARGS_SIZE
+    {
+        return None;
+    }
+    let mut since = [0u8; 8];
+    since.copy_from_slice(&args[20..]);
+    Some(u64::from_le_bytes(since))
+}
+
+// Timestamp metrics are compared with the median time of past blocks by the node,
+// so they are treated as still locked
+fn since_passed(
+    since: u64,
+    block_number: u64,
+    epoch: EpochNumberWithFraction,
+    tip: &ChainTip,
+) -> bool {
+    if since & SINCE_RESERVED_MASK != 0 {
+        return false;
+    }
+    let relative = since & SINCE_RELATIVE_FLAG != 0;
+    let value = since & SINCE_VALUE_MASK;
+    match since & SINCE_METRIC_MASK {
+        SINCE_METRIC_BLOCK_NUMBER if relative => tip.number >= block_number.saturating_add(value),
+        SINCE_METRIC_BLOCK_NUMBER => tip.number >= value,
+        SINCE_METRIC_EPOCH => {
+            let value = EpochNumberWithFraction::from_full_value(value).to_rational();
+            if relative {
+                tip.epoch.to_rational() >= epoch.to_rational() + value
+            } else {
+                tip.epoch.to_rational() >= value
+            }
+        }
+        _ => false,
+    }
+}
+
+fn describe(out_point: &packed::OutPoint) -> String {
+    let tx_hash: H256 = out_point.tx_hash().unpack();
+    let index: u32 = out_point.index().unpack();
+    format!("{:#x}:{}", tx_hash, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::{sighash_script, MULTISIG_TYPE_HASH, SIGHASH_TYPE_HASH};
+    use crate::indexer::IndexerClient;
+    use crate::rpc::{mock, RpcClient};
+    use ckb_jsonrpc_types::{Consensus, HeaderView, ProposalWindow};
+    use serde::Serialize;
+    use serde_json::{json, Value};
+
+    const ONE_CKB: u64 = 100_000_000;
+
+    fn dep(index: u32) -> packed::CellDep {
+        packed::CellDep::new_builder()
+            .out_point(packed::OutPoint::new(Default::default(), index))
+            .build()
+    }
+
+    fn genesis() -> GenesisInfo {
+        GenesisInfo {
+            sighash_type_hash: SIGHASH_TYPE_HASH,
+            multisig_type_hash: MULTISIG_TYPE_HASH,
+            sighash_dep: dep(0),
+            multisig_dep: dep(1),
+        }
+    }
+
+    fn reply(result: impl Serialize) -> String {
+        json!({ "jsonrpc": "2.0", "id": 0, "result": result }).to_string()
+    }
+
+    fn cell(lock: &packed::Script, tx_hash: u8, block_number: u64, tx_index: u32) -> Value {
+        json!({
+            "output": {
+                "capacity": format!("{:#x}", 1000 * ONE_CKB),
+                "lock": ckb_jsonrpc_types::Script::from(lock.clone()),
+                "type": null,
+            },
+            "output_data": "0x",
+            "out_point": {
+                "tx_hash": H256([tx_hash; 32]),
+                "index": "0x0",
+            },
+            "block_number": format!("{:#x}", block_number),
+            "tx_index": format!("{:#x}", tx_index),
+        })
+    }
+
+    fn header(number: u64, epoch: EpochNumberWithFraction) -> HeaderView {
+        core::HeaderBuilder::default()
+            .number(number.pack())
+            .epoch(epoch.full_value().pack())
+            .build()
+            .into()
+    }
+
+    fn consensus(cellbase_maturity: EpochNumberWithFraction) -> Consensus {
+        let ratio = core::RationalU256::from_u256(1u64.into());
+        Consensus {
+            id: "ckb_dev".into(),
+            genesis_hash: H256::default(),
+            dao_type_hash: None,
+            secp256k1_blake160_sighash_all_type_hash: Some(SIGHASH_TYPE_HASH),
+            secp256k1_blake160_multisig_all_type_hash: Some(MULTISIG_TYPE_HASH),
+            initial_primary_epoch_reward: 0.into(),
+            secondary_epoch_reward: 0.into(),
+            max_uncles_num: 0.into(),
+            orphan_rate_target: ratio.clone(),
+            epoch_duration_target: 0.into(),
+            tx_proposal_window: ProposalWindow {
+                closest: 2.into(),
+                farthest: 10.into(),
+            },
+            proposer_reward_ratio: ratio,
+            cellbase_maturity: cellbase_maturity.full_value().into(),
+            median_time_block_count: 37.into(),
+            max_block_cycles: 0.into(),
+            max_block_bytes: 0.into(),
+            block_version: 0.into(),
+            tx_version: 0.into(),
+            type_id_code_hash: H256::default(),
+            max_block_proposals_limit: 0.into(),
+            primary_epoch_reward_halving_interval: 0.into(),
+            permanent_difficulty_in_dummy: true,
+            hardfork_features: vec![],
+        }
+    }
+
+    #[test]
+    fn test_build_adds_change_fee_and_deps() {
+        let sender = sighash_script(&[1u8; 20]);
+        let page = reply(json!({
+            "objects": [cell(&sender, 1, 1, 1)],
+            "last_cursor": "0x01",
+        }));
+        let (url, _server) = mock::serve(vec![page]);
+        let rpc = BlockingRpcClient::new(RpcClient::new(&url)).unwrap();
+        let indexer = BlockingIndexerClient::new(IndexerClient::new(&url)).unwrap();
+
+        let recipient = packed::CellOutput::new_builder()
+            .capacity(Capacity::shannons(100 * ONE_CKB).pack())
+            .lock(sighash_script(&[2u8; 20]))
+            .build();
+        let tx = TransactionBuilder::new(&rpc, &indexer)
+            .genesis(genesis())
+            .from_lock(sender.clone())
+            .output(recipient, Bytes::new())
+            .build()
+            .unwrap();
+
+        assert_eq!(tx.inputs().len(), 1);
+        assert_eq!(tx.cell_deps_iter().collect::<Vec<_>>(), vec![dep(0)]);
+        let change = tx.outputs().get(1).unwrap();
+        assert_eq!(change.lock(), sender);
+        let fee = FeeRate::from_u64(DEFAULT_FEE_RATE)
+            .fee(tx.data().serialized_size_in_block())
+            .as_u64();
+        let change_capacity: u64 = change.capacity().unpack();
+        assert_eq!(change_capacity, 900 * ONE_CKB - fee);
+        let witness =
+            packed::WitnessArgs::from_slice(&tx.witnesses().get(0).unwrap().raw_data()).unwrap();
+        assert_eq!(
+            witness.lock().to_opt().unwrap().raw_data().len(),
+            SIGNATURE_SIZE
+        );
+    }
+
+    #[test]
+    fn test_build_skips_prefix_matches_and_immature_cellbases() {
+        let sender = sighash_script(&[1u8; 20]);
+        let longer = sender
+            .clone()
+            .as_builder()
+            .args(Bytes::from(vec![1u8; 28]).pack())
+            .build();
+        let page = reply(json!({
+            "objects": [cell(&longer, 1, 1, 1), cell(&sender, 2, 5, 0), cell(&sender, 3, 6, 1)],
+            "last_cursor": "0x03",
+        }));
+        let bodies = vec![
+            page,
+            reply(header(10, EpochNumberWithFraction::new(0, 10, 1000))),
+            reply(consensus(EpochNumberWithFraction::new(4, 0, 1))),
+            reply(header(5, EpochNumberWithFraction::new(0, 5, 1000))),
+        ];
+        let (url, server) = mock::serve(bodies);
+        let rpc = BlockingRpcClient::new(RpcClient::new(&url)).unwrap();
+        let indexer = BlockingIndexerClient::new(IndexerClient::new(&url)).unwrap();
+
+        let recipient = packed::CellOutput::new_builder()
+            .capacity(Capacity::shannons(100 * ONE_CKB).pack())
+            .lock(sighash_script(&[2u8; 20]))
+            .build();
+        let tx = TransactionBuilder::new(&rpc, &indexer)
+            .genesis(genesis())
+            .from_lock(sender)
+            .output(recipient, Bytes::new())
+            .build()
+            .unwrap();
+
+        let inputs = tx.inputs().into_iter().collect::<Vec<_>>();
+        assert_eq!(inputs.len(), 1);
+        let tx_hash: H256 = inputs[0].previous_output().tx_hash().unpack();
+        assert_eq!(tx_hash, H256([3u8; 32]));
+        let methods = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|request| serde_json::from_str::<Value>(request).unwrap()["method"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            methods,
+            vec![
+                "get_cells",
+                "get_tip_header",
+                "get_consensus",
+                "get_header_by_number"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_since_passed() {
+        let tip = ChainTip {
+            number: 100,
+            epoch: EpochNumberWithFraction::new(5, 0, 1000),
+            cellbase_maturity: EpochNumberWithFraction::new(4, 0, 1),
+        };
+        let epoch = EpochNumberWithFraction::new(2, 0, 1000);
+        assert!(since_passed(100, 50, epoch, &tip));
+        assert!(!since_passed(101, 50, epoch, &tip));
+        assert!(since_passed(SINCE_RELATIVE_FLAG | 50, 50, epoch, &tip));
+        assert!(!since_passed(SINCE_RELATIVE_FLAG | 51, 50, epoch, &tip));
+        let three_epochs = EpochNumberWithFraction::new(3, 0, 1).full_value();
+        assert!(since_passed(
+            SINCE_RELATIVE_FLAG | SINCE_METRIC_EPOCH | three_epochs,
+            50,
+            epoch,
+            &tip
+        ));
+        let six_epochs = EpochNumberWithFraction::new(6, 0, 1).full_value();
+        assert!(!since_passed(
+            SINCE_METRIC_EPOCH | six_epochs,
+            50,
+            epoch,
+            &tip
+        ));
+        // Timestamps are never considered passed
+        assert!(!since_passed(0b10 << 61, 50, epoch, &tip));
+    }
+}
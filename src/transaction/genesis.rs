@@ -0,0 +1,94 @@
+use super::{TransactionError, TransactionResult};
+use crate::rpc::BlockingRpcClient;
+
+use ckb_jsonrpc_types::BlockView;
+use ckb_types::{
+    core::{DepType, ScriptHashType},
+    packed,
+    prelude::*,
+    H256,
+};
+
+// Positions of the system cells in the genesis block, as (transaction, output)
+const SIGHASH_OUTPUT: (usize, usize) = (0, 1);
+const MULTISIG_OUTPUT: (usize, usize) = (0, 4);
+const SIGHASH_GROUP_OUTPUT: (usize, usize) = (1, 0);
+const MULTISIG_GROUP_OUTPUT: (usize, usize) = (1, 1);
+
+/// System scripts deployed in the genesis block, and the dep groups needed to use them.
+#[derive(Debug, Clone)]
+pub struct GenesisInfo {
+    pub sighash_type_hash: H256,
+    pub multisig_type_hash: H256,
+    pub sighash_dep: packed::CellDep,
+    pub multisig_dep: packed::CellDep,
+}
+
+impl GenesisInfo {
+    pub fn load(rpc: &BlockingRpcClient) -> TransactionResult<Self> {
+        let genesis = rpc
+            .get_block_by_number(0)?
+            .ok_or_else(|| TransactionError::InvalidGenesis("missing genesis block".into()))?;
+        Self::from_block(&genesis)
+    }
+
+    pub fn from_block(genesis: &BlockView) -> TransactionResult<Self> {
+        Ok(GenesisInfo {
+            sighash_type_hash: type_hash(genesis, SIGHASH_OUTPUT)?,
+            multisig_type_hash: type_hash(genesis, MULTISIG_OUTPUT)?,
+            sighash_dep: dep_group(genesis, SIGHASH_GROUP_OUTPUT)?,
+            multisig_dep: dep_group(genesis, MULTISIG_GROUP_OUTPUT)?,
+        })
+    }
+
+    /// Cell dep needed to unlock `lock`, if it is a system script.
+    pub fn cell_dep(&self, lock: &packed::Script) -> Option<packed::CellDep> {
+        let type_hash_type: packed::Byte = ScriptHashType::Type.into();
+        if lock.hash_type() != type_hash_type {
+            return None;
+        }
+        let code_hash: H256 = lock.code_hash().unpack();
+        if code_hash == self.sighash_type_hash {
+            Some(self.sighash_dep.clone())
+        } else if code_hash == self.multisig_type_hash {
+            Some(self.multisig_dep.clone())
+        } else {
+            None
+        }
+    }
+}
+
+fn type_hash(genesis: &BlockView, (tx_index, index): (usize, usize)) -> TransactionResult<H256> {
+    genesis
+        .transactions
+        .get(tx_index)
+        .and_then(|tx| tx.inner.outputs.get(index))
+        .and_then(|output| output.type_.clone())
+        .map(|script| packed::Script::from(script).calc_script_hash().unpack())
+        .ok_or_else(|| {
+            TransactionError::InvalidGenesis(format!(
+                "no type script on output {} of transaction {}",
+                index, tx_index
+            ))
+        })
+}
+
+fn dep_group(
+    genesis: &BlockView,
+    (tx_index, index): (usize, usize),
+) -> TransactionResult<packed::CellDep> {
+    let tx = genesis
+        .transactions
+        .get(tx_index)
+        .filter(|tx| tx.inner.outputs.len() > index)
+        .ok_or_else(|| {
+            TransactionError::InvalidGenesis(format!(
+                "missing dep group {} of transaction {}",
+                index, tx_index
+            ))
+        })?;
+    Ok(packed::CellDep::new_builder()
+        .out_point(packed::OutPoint::new(tx.hash.pack(), index as u32))
+        .dep_type(DepType::DepGroup.into())
+        .build())
+}
@@ -0,0 +1,423 @@
+use super::{TransactionError, TransactionResult};
+use crate::address::{sighash_script, MULTISIG_TYPE_HASH};
+use crate::rpc::BlockingRpcClient;
+
+use ckb_crypto::secp::Privkey;
+use ckb_hash::{blake2b_256, new_blake2b};
+use ckb_types::{
+    bytes::{BufMut, Bytes, BytesMut},
+    core::{ScriptHashType, TransactionView},
+    packed,
+    prelude::*,
+    H256,
+};
+
+/// Size of a recoverable secp256k1 signature.
+pub const SIGNATURE_SIZE: usize = 65;
+const MULTISIG_FORMAT_VERSION: u8 = 0;
+
+/// Parameters of the standard secp256k1-blake160 multisig lock: any `threshold` of the
+/// `lock_args` keys may sign, and the first `require_first_n` of them always must.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigConfig {
+    pub require_first_n: u8,
+    pub threshold: u8,
+    pub lock_args: Vec<[u8; 20]>,
+}
+
+impl MultisigConfig {
+    pub fn new(
+        require_first_n: u8,
+        threshold: u8,
+        lock_args: Vec<[u8; 20]>,
+    ) -> TransactionResult<Self> {
+        let invalid = |reason: &str| TransactionError::InvalidMultisig(reason.to_string());
+        if lock_args.is_empty() || lock_args.len() > u8::MAX as usize {
+            return Err(invalid("between 1 and 255 keys are required"));
+        }
+        if threshold == 0 || threshold as usize > lock_args.len() {
+            return Err(invalid(
+                "the threshold must be between 1 and the number of keys",
+            ));
+        }
+        if require_first_n > threshold {
+            return Err(invalid("require_first_n cannot exceed the threshold"));
+        }
+        for (index, lock_arg) in lock_args.iter().enumerate() {
+            if lock_args[..index].contains(lock_arg) {
+                return Err(invalid("keys must be distinct"));
+            }
+        }
+        Ok(MultisigConfig {
+            require_first_n,
+            threshold,
+            lock_args,
+        })
+    }
+
+    /// Parses the `S | R | M | N | blake160(pubkey)...` header of a multisig witness lock.
+    pub fn from_witness_lock(lock: &[u8]) -> Option<Self> {
+        let (&version, rest) = lock.split_first()?;
+        if version != MULTISIG_FORMAT_VERSION || rest.len() < 3 {
+            return None;
+        }
+        let count = rest[2] as usize;
+        let keys = rest.get(3..3 + 20 * count)?;
+        let lock_args = keys
+            .chunks(20)
+            .map(|chunk| {
+                let mut lock_arg = [0u8; 20];
+                lock_arg.copy_from_slice(chunk);
+                lock_arg
+            })
+            .collect();
+        Self::new(rest[0], rest[1], lock_args).ok()
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut bytes = BytesMut::with_capacity(4 + 20 * self.lock_args.len());
+        bytes.put_u8(MULTISIG_FORMAT_VERSION);
+        bytes.put_u8(self.require_first_n);
+        bytes.put_u8(self.threshold);
+        bytes.put_u8(self.lock_args.len() as u8);
+        for lock_arg in &self.lock_args {
+            bytes.put_slice(lock_arg);
+        }
+        bytes.freeze()
+    }
+
+    pub fn hash160(&self) -> [u8; 20] {
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&blake2b_256(self.to_bytes())[0..20]);
+        hash
+    }
+
+    pub fn lock_script(&self) -> packed::Script {
+        packed::Script::new_builder()
+            .code_hash(MULTISIG_TYPE_HASH.pack())
+            .hash_type(ScriptHashType::Type.into())
+            .args(Bytes::from(self.hash160().to_vec()).pack())
+            .build()
+    }
+
+    /// Size of the witness lock once `threshold` signatures are added.
+    pub fn witness_lock_size(&self) -> usize {
+        4 + 20 * self.lock_args.len() + SIGNATURE_SIZE * self.threshold as usize
+    }
+
+    fn placeholder(&self) -> Bytes {
+        let mut lock = BytesMut::from(&self.to_bytes()[..]);
+        lock.resize(self.witness_lock_size(), 0);
+        lock.freeze()
+    }
+
+    // Multisig locks may append an 8 byte since to the hash
+    fn matches(&self, lock: &packed::Script) -> bool {
+        let args = lock.args().raw_data();
+        is_type_script(lock, &MULTISIG_TYPE_HASH)
+            && (args.len() == 20 || args.len() == 28)
+            && args[0..20] == self.hash160()
+    }
+}
+
+/// Signs transactions with one secp256k1 key, for its sighash-all lock and for
+/// any configured multisig lock listing it.
+pub struct Signer {
+    privkey: Privkey,
+    lock_arg: [u8; 20],
+    multisig: Vec<MultisigConfig>,
+}
+
+impl Signer {
+    pub fn new(secret: &[u8; 32]) -> TransactionResult<Self> {
+        let privkey = Privkey::from_slice(secret);
+        let mut lock_arg = [0u8; 20];
+        lock_arg.copy_from_slice(&blake2b_256(privkey.pubkey()?.serialize())[0..20]);
+        Ok(Signer {
+            privkey,
+            lock_arg,
+            multisig: vec![],
+        })
+    }
+
+    /// Also signs inputs locked by `config`. Configs found in the witness of
+    /// a partially signed transaction are used without being registered.
+    pub fn multisig(mut self, config: MultisigConfig) -> Self {
+        self.multisig.push(config);
+        self
+    }
+
+    pub fn lock_arg(&self) -> [u8; 20] {
+        self.lock_arg
+    }
+
+    /// Signs every input group this key can unlock. `input_locks` holds the lock of
+    /// each input, in order, as returned by [`input_locks`].
+    pub fn sign(
+        &self,
+        tx: &TransactionView,
+        input_locks: &[packed::Script],
+    ) -> TransactionResult<TransactionView> {
+        let own_lock = sighash_script(&self.lock_arg);
+        let mut witnesses = tx.witnesses().into_iter().collect::<Vec<_>>();
+        if witnesses.len() < input_locks.len() {
+            witnesses.resize(input_locks.len(), Bytes::new().pack());
+        }
+
+        let mut signed = 0;
+        for (lock, group) in script_groups(input_locks) {
+            let first_witness = witness_args(&witnesses[group[0]])?;
+            let lock_bytes = if lock == own_lock {
+                let placeholder = Bytes::from(vec![0u8; SIGNATURE_SIZE]);
+                let signature =
+                    self.sign_group(tx, &group, &witnesses, &first_witness, placeholder)?;
+                Bytes::from(signature.to_vec())
+            } else if let Some(config) = self.multisig_config(&lock, &first_witness) {
+                if !config.lock_args.contains(&self.lock_arg) {
+                    continue;
+                }
+                let signature =
+                    self.sign_group(tx, &group, &witnesses, &first_witness, config.placeholder())?;
+                add_multisig_signature(&config, &first_witness, &signature)?
+            } else {
+                continue;
+            };
+            witnesses[group[0]] = first_witness
+                .as_builder()
+                .lock(Some(lock_bytes).pack())
+                .build()
+                .as_bytes()
+                .pack();
+            signed += 1;
+        }
+
+        if signed == 0 {
+            return Err(TransactionError::NothingToSign(format!(
+                "0x{}",
+                hex::encode(self.lock_arg)
+            )));
+        }
+        Ok(tx.as_advanced_builder().set_witnesses(witnesses).build())
+    }
+
+    fn multisig_config(
+        &self,
+        lock: &packed::Script,
+        first_witness: &packed::WitnessArgs,
+    ) -> Option<MultisigConfig> {
+        let from_witness = first_witness
+            .lock()
+            .to_opt()
+            .and_then(|lock| MultisigConfig::from_witness_lock(&lock.raw_data()));
+        self.multisig
+            .iter()
+            .cloned()
+            .chain(from_witness)
+            .find(|config| config.matches(lock))
+    }
+
+    fn sign_group(
+        &self,
+        tx: &TransactionView,
+        group: &[usize],
+        witnesses: &[packed::Bytes],
+        first_witness: &packed::WitnessArgs,
+        placeholder: Bytes,
+    ) -> TransactionResult<[u8; SIGNATURE_SIZE]> {
+        let first_witness = first_witness
+            .clone()
+            .as_builder()
+            .lock(Some(placeholder).pack())
+            .build();
+        let message = sighash_all_message(tx, group, witnesses, &first_witness);
+        let mut signature = [0u8; SIGNATURE_SIZE];
+        signature.copy_from_slice(&self.privkey.sign_recoverable(&message)?.serialize());
+        Ok(signature)
+    }
+}
+
+/// The sighash-all message of a script group: the transaction hash followed by the
+/// length-prefixed witnesses of the group, with `first_witness` standing in for the
+/// first one, and of any inputs beyond the inputs.
+pub fn sighash_all_message(
+    tx: &TransactionView,
+    group: &[usize],
+    witnesses: &[packed::Bytes],
+    first_witness: &packed::WitnessArgs,
+) -> H256 {
+    let mut hasher = new_blake2b();
+    hasher.update(tx.hash().as_slice());
+    let mut hash_witness = |witness: &[u8]| {
+        hasher.update(&(witness.len() as u64).to_le_bytes());
+        hasher.update(witness);
+    };
+    hash_witness(first_witness.as_slice());
+    for index in &group[1..] {
+        hash_witness(&witnesses[*index].raw_data());
+    }
+    for witness in witnesses.iter().skip(tx.inputs().len()) {
+        hash_witness(&witness.raw_data());
+    }
+    let mut message = [0u8; 32];
+    hasher.finalize(&mut message);
+    H256::from(message)
+}
+
+/// Locks of the cells spent by `tx`, looked up in the transactions that created them.
+pub fn input_locks(
+    rpc: &BlockingRpcClient,
+    tx: &TransactionView,
+) -> TransactionResult<Vec<packed::Script>> {
+    tx.input_pts_iter()
+        .map(|out_point| {
+            let tx_hash: H256 = out_point.tx_hash().unpack();
+            let index: u32 = out_point.index().unpack();
+            rpc.get_transaction(&tx_hash)?
+                .and_then(|status| status.transaction)
+                .and_then(|previous| previous.inner.outputs.get(index as usize).cloned())
+                .map(|output| packed::Script::from(output.lock))
+                .ok_or_else(|| TransactionError::UnknownInput(format!("{:#x}:{}", tx_hash, index)))
+        })
+        .collect()
+}
+
+// Input indices grouped by lock, in order of first appearance
+fn script_groups(input_locks: &[packed::Script]) -> Vec<(packed::Script, Vec<usize>)> {
+    let mut groups: Vec<(packed::Script, Vec<usize>)> = vec![];
+    for (index, lock) in input_locks.iter().enumerate() {
+        match groups.iter_mut().find(|(script, _)| script == lock) {
+            Some((_, group)) => group.push(index),
+            None => groups.push((lock.clone(), vec![index])),
+        }
+    }
+    groups
+}
+
+fn witness_args(witness: &packed::Bytes) -> TransactionResult<packed::WitnessArgs> {
+    let raw = witness.raw_data();
+    if raw.is_empty() {
+        return Ok(packed::WitnessArgs::default());
+    }
+    packed::WitnessArgs::from_slice(&raw)
+        .map_err(|e| TransactionError::InvalidWitness(e.to_string()))
+}
+
+// Fills the first empty signature slot, keeping signatures added by other keys
+fn add_multisig_signature(
+    config: &MultisigConfig,
+    witness: &packed::WitnessArgs,
+    signature: &[u8; SIGNATURE_SIZE],
+) -> TransactionResult<Bytes> {
+    let header_size = 4 + 20 * config.lock_args.len();
+    let mut lock = match witness.lock().to_opt() {
+        Some(lock) if lock.raw_data().starts_with(&config.to_bytes()) => {
+            BytesMut::from(&lock.raw_data()[..])
+        }
+        _ => BytesMut::from(&config.placeholder()[..]),
+    };
+    lock.resize(config.witness_lock_size(), 0);
+
+    let slot = |index: usize| {
+        let start = header_size + index * SIGNATURE_SIZE;
+        start..start + SIGNATURE_SIZE
+    };
+    let slots = config.threshold as usize;
+    if (0..slots).any(|index| lock[slot(index)] == signature[..]) {
+        return Ok(lock.freeze());
+    }
+    let empty = (0..slots)
+        .find(|index| lock[slot(*index)].iter().all(|byte| *byte == 0))
+        .ok_or_else(|| {
+            TransactionError::InvalidMultisig(format!(
+                "already holds {} signatures",
+                config.threshold
+            ))
+        })?;
+    lock[slot(empty)].copy_from_slice(signature);
+    Ok(lock.freeze())
+}
+
+fn is_type_script(lock: &packed::Script, code_hash: &H256) -> bool {
+    let type_hash_type: packed::Byte = ScriptHashType::Type.into();
+    let lock_code_hash: H256 = lock.code_hash().unpack();
+    lock.hash_type() == type_hash_type && &lock_code_hash == code_hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_crypto::secp::Pubkey;
+    use ckb_types::core;
+
+    const SECRETS: [[u8; 32]; 2] = [[1u8; 32], [2u8; 32]];
+
+    fn recover(message: &H256, signature: &[u8]) -> Pubkey {
+        ckb_crypto::secp::Signature::from_slice(signature)
+            .unwrap()
+            .recover(message)
+            .unwrap()
+    }
+
+    fn spend(lock: &packed::Script, inputs: usize) -> (TransactionView, Vec<packed::Script>) {
+        let tx = core::TransactionBuilder::default()
+            .inputs((0..inputs).map(|index| {
+                packed::CellInput::new(packed::OutPoint::new(Default::default(), index as u32), 0)
+            }))
+            .build();
+        (tx, vec![lock.clone(); inputs])
+    }
+
+    #[test]
+    fn test_sign_sighash_group() {
+        let signer = Signer::new(&SECRETS[0]).unwrap();
+        let (tx, locks) = spend(&sighash_script(&signer.lock_arg()), 2);
+        let signed = signer.sign(&tx, &locks).unwrap();
+
+        let witnesses = signed.witnesses().into_iter().collect::<Vec<_>>();
+        assert!(witnesses[1].raw_data().is_empty());
+        let witness = witness_args(&witnesses[0]).unwrap();
+        let signature = witness.lock().to_opt().unwrap().raw_data();
+        let placeholder = witness
+            .as_builder()
+            .lock(Some(Bytes::from(vec![0u8; SIGNATURE_SIZE])).pack())
+            .build();
+        let message = sighash_all_message(&signed, &[0, 1], &witnesses, &placeholder);
+        let pubkey = recover(&message, &signature);
+        assert_eq!(blake2b_256(pubkey.serialize())[0..20], signer.lock_arg());
+    }
+
+    #[test]
+    fn test_multisig_collects_signatures() {
+        let signers = SECRETS
+            .iter()
+            .map(|secret| Signer::new(secret).unwrap())
+            .collect::<Vec<_>>();
+        let config =
+            MultisigConfig::new(0, 2, signers.iter().map(Signer::lock_arg).collect()).unwrap();
+        let (tx, locks) = spend(&config.lock_script(), 1);
+
+        // The second signer finds the config in the witness left by the first
+        let partial = Signer::new(&SECRETS[0])
+            .unwrap()
+            .multisig(config.clone())
+            .sign(&tx, &locks)
+            .unwrap();
+        let signed = signers[1].sign(&partial, &locks).unwrap();
+
+        let lock = witness_args(&signed.witnesses().get(0).unwrap())
+            .unwrap()
+            .lock()
+            .to_opt()
+            .unwrap()
+            .raw_data();
+        assert_eq!(lock.len(), config.witness_lock_size());
+        assert_eq!(
+            MultisigConfig::from_witness_lock(&lock),
+            Some(config.clone())
+        );
+        let signatures = &lock[4 + 20 * config.lock_args.len()..];
+        assert!(signatures
+            .chunks(SIGNATURE_SIZE)
+            .all(|signature| signature.iter().any(|byte| *byte != 0)));
+    }
+}
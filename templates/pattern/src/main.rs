@@ -0,0 +1,106 @@
+//! Generated by `trampoline pattern compile {{ pattern }}` from `generators/{{ pattern }}.toml`.
+//! Compile the pattern again rather than editing this file.
+#![no_std]
+#![no_main]
+#![feature(lang_items)]
+#![feature(alloc_error_handler)]
+#![feature(panic_info_message)]
+#![allow(dead_code, unused_imports)]
+{% for schema in schemas %}
+mod {{ schema }};{% endfor %}
+
+use ckb_std::ckb_constants::Source;
+use ckb_std::error::SysError;
+use ckb_std::high_level::{
+    load_cell_capacity, load_cell_data, load_cell_lock_hash, load_cell_type_hash, QueryIter,
+};
+use molecule::prelude::Reader;
+
+ckb_std::entry!(program_entry);
+ckb_std::default_alloc!();
+
+// Outputs described by the pattern. One more output is allowed for the change
+const OUTPUTS: usize = {{ outputs | length }};
+
+#[repr(i8)]
+enum Error {
+    IndexOutOfBound = 1,
+    ItemMissing,
+    LengthNotEnough,
+    Encoding,
+    TooFewInputs,
+    OutputCount,
+    Capacity,
+    Lock,
+    Type,
+    Data,
+}
+
+impl From<SysError> for Error {
+    fn from(err: SysError) -> Self {
+        match err {
+            SysError::IndexOutOfBound => Self::IndexOutOfBound,
+            SysError::ItemMissing => Self::ItemMissing,
+            SysError::LengthNotEnough(_) => Self::LengthNotEnough,
+            SysError::Encoding => Self::Encoding,
+            SysError::Unknown(err_code) => panic!("unexpected sys error {}", err_code),
+        }
+    }
+}
+
+fn program_entry() -> i8 {
+    match verify() {
+        Ok(()) => 0,
+        Err(err) => err as i8,
+    }
+}
+
+fn verify() -> Result<(), Error> {
+    let outputs = QueryIter::new(load_cell_capacity, Source::Output).count();
+    if outputs != OUTPUTS && outputs != OUTPUTS + 1 {
+        return Err(Error::OutputCount);
+    }
+{% for input in inputs %}
+    // Input {{ input.index }}: at least {{ input.count }} cells with this type script
+    let type_hash: [u8; 32] = {{ input.type_hash }};
+    let matching = QueryIter::new(load_cell_type_hash, Source::Input)
+        .filter(|hash| hash.as_ref() == Some(&type_hash))
+        .count();
+    if matching < {{ input.count }} {
+        return Err(Error::TooFewInputs);
+    }
+{% endfor %}{% if input_capacity is defined %}
+    // Total input capacity {{ input_capacity }} total output capacity
+    let input_capacity: u64 = QueryIter::new(load_cell_capacity, Source::Input).sum();
+    let output_capacity: u64 = QueryIter::new(load_cell_capacity, Source::Output).sum();
+    if !(input_capacity {{ input_capacity }} output_capacity) {
+        return Err(Error::Capacity);
+    }
+{% endif %}{% for output in outputs %}
+    // Output {{ output.index }}{% if output.capacity is defined %}
+    if load_cell_capacity({{ output.index }}, Source::Output)? != {{ output.capacity }} {
+        return Err(Error::Capacity);
+    }{% endif %}{% if output.min_capacity is defined %}
+    if load_cell_capacity({{ output.index }}, Source::Output)? < {{ output.min_capacity }} {
+        return Err(Error::Capacity);
+    }{% endif %}{% if output.max_capacity is defined %}
+    if load_cell_capacity({{ output.index }}, Source::Output)? > {{ output.max_capacity }} {
+        return Err(Error::Capacity);
+    }{% endif %}{% if output.lock_hash %}
+    let lock_hash: [u8; 32] = {{ output.lock_hash }};
+    if load_cell_lock_hash({{ output.index }}, Source::Output)? != lock_hash {
+        return Err(Error::Lock);
+    }{% endif %}{% if output.type_hash %}
+    let type_hash: [u8; 32] = {{ output.type_hash }};
+    if load_cell_type_hash({{ output.index }}, Source::Output)? != Some(type_hash) {
+        return Err(Error::Type);
+    }{% endif %}{% if output.data %}
+    if load_cell_data({{ output.index }}, Source::Output)?.as_slice() != &{{ output.data }}[..] {
+        return Err(Error::Data);
+    }{% endif %}{% if output.reader %}
+    if {{ output.reader }}::verify(&load_cell_data({{ output.index }}, Source::Output)?, false).is_err() {
+        return Err(Error::Data);
+    }{% endif %}
+{% endfor %}
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use ckb_std::error::SysError;
+
+/// Exit codes of the script. 1 to 4 are reserved for syscall errors.
+#[repr(i8)]
+pub enum Error {
+    IndexOutOfBound = 1,
+    ItemMissing,
+    LengthNotEnough,
+    Encoding,
+    MissingArgs,
+}
+
+impl From<SysError> for Error {
+    fn from(err: SysError) -> Self {
+        match err {
+            SysError::IndexOutOfBound => Self::IndexOutOfBound,
+            SysError::ItemMissing => Self::ItemMissing,
+            SysError::LengthNotEnough(_) => Self::LengthNotEnough,
+            SysError::Encoding => Self::Encoding,
+            SysError::Unknown(err_code) => panic!("unexpected sys error {}", err_code),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! The {{ name }} script. Build it with `trampoline script build {{ name }}`.
+#![no_std]
+#![no_main]
+#![feature(lang_items)]
+#![feature(alloc_error_handler)]
+#![feature(panic_info_message)]
+
+mod error;
+
+use ckb_std::ckb_types::{bytes::Bytes, prelude::*};
+use ckb_std::high_level::load_script;
+use error::Error;
+
+ckb_std::entry!(program_entry);
+ckb_std::default_alloc!();
+
+fn program_entry() -> i8 {
+    match verify() {
+        Ok(()) => 0,
+        Err(err) => err as i8,
+    }
+}
+
+fn verify() -> Result<(), Error> {
+    let script = load_script()?;
+    let args: Bytes = script.args().unpack();
+    // Replace with the rules of your script
+    if args.is_empty() {
+        return Err(Error::MissingArgs);
+    }
+    Ok(())
+}
@@ -0,0 +1,20 @@
+//! Helpers shared by the script tests in `tests/`.
+use ckb_types::packed::OutPoint;
+use trampoline::project::TrampolineProject;
+use trampoline::testing::{test_profile, Context};
+use trampoline::TrampolineResource;
+
+pub fn project() -> TrampolineProject {
+    TrampolineProject::load(env!("CARGO_MANIFEST_DIR"))
+        .expect("tests crate outside a trampoline project")
+        .into()
+}
+
+/// A fresh context with the built binary of the script `name` deployed.
+pub fn deploy(name: &str) -> (Context, OutPoint) {
+    let mut context = Context::new();
+    let out_point = context
+        .deploy_script(&project(), name, test_profile())
+        .unwrap_or_else(|e| panic!("cannot deploy {}: {}", name, e));
+    (context, out_point)
+}
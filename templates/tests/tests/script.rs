@@ -0,0 +1,47 @@
+//! Tests for the {{ name }} script, run offline in the CKB VM.
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, TransactionView},
+    packed,
+    prelude::*,
+};
+use trampoline::testing::{Context, DEFAULT_MAX_CYCLES};
+use {{ project | replace(from="-", to="_") }}_tests::deploy;
+
+// Spends a cell locked by the script with `args`, sending part of it back to the same lock
+fn transaction(context: &mut Context, code: &packed::OutPoint, args: &[u8]) -> TransactionView {
+    let lock = context
+        .build_script(code, Bytes::copy_from_slice(args))
+        .expect("deployed script");
+    let cell = packed::CellOutput::new_builder()
+        .capacity(Capacity::bytes(1000).unwrap().pack())
+        .lock(lock.clone())
+        .build();
+    context.create_cell(cell.clone(), Bytes::new());
+    let output = cell
+        .as_builder()
+        .capacity(Capacity::bytes(100).unwrap().pack())
+        .build();
+    let builder = context
+        .transaction()
+        .from_lock(lock)
+        .output(output, Bytes::new());
+    context.build(builder).expect("balanced transaction")
+}
+
+#[test]
+fn test_{{ name | replace(from="-", to="_") }}_accepts_args() {
+    let (mut context, code) = deploy("{{ name }}");
+    let tx = transaction(&mut context, &code, &[1]);
+    let verification = context.verify(&tx, DEFAULT_MAX_CYCLES).unwrap();
+    assert!(verification.is_success(), "{}", verification);
+    println!("{}", verification);
+}
+
+#[test]
+fn test_{{ name | replace(from="-", to="_") }}_rejects_empty_args() {
+    let (mut context, code) = deploy("{{ name }}");
+    let tx = transaction(&mut context, &code, &[]);
+    let verification = context.verify(&tx, DEFAULT_MAX_CYCLES).unwrap();
+    assert!(!verification.is_success(), "{}", verification);
+}